@@ -0,0 +1,69 @@
+//! Compare memory use and parse time of the H.4.1 observations held as a
+//! balance sheet per date against the columnar store.
+//!
+//! Run with `cargo bench --bench columnar`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use mulligan::fed::{parse_h41_data, parse_h41_store};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const FIXTURES: [&str; 3] = [
+    "tests/data/FRB_H41_2006.xml",
+    "tests/data/FRB_H41_2010.xml",
+    "tests/data/FRB_H41_2020.xml",
+];
+
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Bytes kept alive by the result of `f`.
+fn live_bytes<T>(f: impl Fn() -> T) -> usize {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let value = f();
+    let live = ALLOCATED.load(Ordering::Relaxed).saturating_sub(before);
+    drop(value);
+    live
+}
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for fixture in FIXTURES {
+        let text = fs::read_to_string(fixture).unwrap();
+        let map_bytes = live_bytes(|| parse_h41_data(&text).unwrap());
+        let store_bytes = live_bytes(|| parse_h41_store(&text).unwrap());
+        println!("{fixture}: map {map_bytes} bytes, store {store_bytes} bytes");
+        assert!(
+            store_bytes < map_bytes,
+            "{fixture}: the store takes {store_bytes} bytes, the map {map_bytes}"
+        );
+
+        group.bench_with_input(BenchmarkId::new("map", fixture), &text, |b, text| {
+            b.iter(|| parse_h41_data(text).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("store", fixture), &text, |b, text| {
+            b.iter(|| parse_h41_store(text).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -0,0 +1,350 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek};
+use std::path::{Path, PathBuf};
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+const DEFAULT_MAX_ENTRIES: usize = 64;
+const DEFAULT_MAX_TOTAL_BYTES: u64 = 1 << 30;
+const DEFAULT_MAX_COMPRESSION_RATIO: u64 = 200;
+const FILE_TYPE_MASK: u32 = 0o170000;
+const SYMLINK_FILE_TYPE: u32 = 0o120000;
+const PERMISSIONS_MASK: u32 = 0o777;
+
+/// Limits applied when extracting a zip archive.
+#[derive(Clone, Debug)]
+pub struct ExtractOptions {
+    /// maximum number of entries in the archive.
+    pub max_entries: usize,
+    /// maximum number of decompressed bytes written to disk.
+    pub max_total_bytes: u64,
+    /// maximum ratio between the decompressed and compressed size of an entry.
+    pub max_compression_ratio: u64,
+    /// apply the unix permissions stored in the archive (without special bits).
+    pub preserve_permissions: bool,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> ExtractOptions {
+        ExtractOptions {
+            max_entries: DEFAULT_MAX_ENTRIES,
+            max_total_bytes: DEFAULT_MAX_TOTAL_BYTES,
+            max_compression_ratio: DEFAULT_MAX_COMPRESSION_RATIO,
+            preserve_permissions: false,
+        }
+    }
+}
+
+/// Error returned when an archive cannot be safely extracted.
+#[derive(Debug)]
+pub enum ExtractError {
+    /// I/O error reading the archive or writing the extracted files.
+    Io(io::Error),
+    /// malformed zip archive.
+    Zip(ZipError),
+    /// archive with more entries than allowed.
+    TooManyEntries(usize),
+    /// entry whose name is absolute or escapes the destination directory.
+    UnsafePath(String),
+    /// entry that is a symbolic link.
+    Symlink(String),
+    /// entry whose compression ratio is above the allowed one.
+    CompressionRatio(String),
+    /// archive decompressing to more bytes than allowed.
+    TooLarge(u64),
+}
+
+impl fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtractError::Io(e) => write!(f, "I/O error extracting archive: {e}"),
+            ExtractError::Zip(e) => write!(f, "invalid zip archive: {e}"),
+            ExtractError::TooManyEntries(n) => write!(f, "archive has too many entries: {n}"),
+            ExtractError::UnsafePath(name) => write!(f, "archive entry with unsafe path: {name}"),
+            ExtractError::Symlink(name) => write!(f, "archive entry is a symlink: {name}"),
+            ExtractError::CompressionRatio(name) => {
+                write!(f, "archive entry with suspicious compression ratio: {name}")
+            }
+            ExtractError::TooLarge(max) => {
+                write!(f, "archive decompresses to more than {max} bytes")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExtractError {}
+
+impl From<io::Error> for ExtractError {
+    fn from(e: io::Error) -> ExtractError {
+        ExtractError::Io(e)
+    }
+}
+
+impl From<ZipError> for ExtractError {
+    fn from(e: ZipError) -> ExtractError {
+        ExtractError::Zip(e)
+    }
+}
+
+/// File written while extracting an archive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExtractedFile {
+    /// entry name relative to the destination directory.
+    pub name: String,
+    /// path the entry was written to.
+    pub path: PathBuf,
+    /// number of bytes written.
+    pub size: u64,
+}
+
+fn check_entries<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    options: &ExtractOptions,
+) -> Result<(), ExtractError> {
+    if archive.len() > options.max_entries {
+        return Err(ExtractError::TooManyEntries(archive.len()));
+    }
+    let mut total_bytes: u64 = 0;
+    for i in 0..archive.len() {
+        let file = archive.by_index_raw(i)?;
+        if file.enclosed_name().is_none() {
+            return Err(ExtractError::UnsafePath(file.name().to_string()));
+        }
+        if file.unix_mode().map(|m| m & FILE_TYPE_MASK) == Some(SYMLINK_FILE_TYPE) {
+            return Err(ExtractError::Symlink(file.name().to_string()));
+        }
+        if file.size()
+            > file
+                .compressed_size()
+                .max(1)
+                .saturating_mul(options.max_compression_ratio)
+        {
+            return Err(ExtractError::CompressionRatio(file.name().to_string()));
+        }
+        total_bytes = total_bytes.saturating_add(file.size());
+        if total_bytes > options.max_total_bytes {
+            return Err(ExtractError::TooLarge(options.max_total_bytes));
+        }
+    }
+    Ok(())
+}
+
+// Reader of an entry stopping one byte past `limit`, so exceeding the
+// limit can be told apart from reaching it.
+fn take_limit<R: Read>(reader: R, limit: u64) -> io::Take<R> {
+    reader.take(limit.saturating_add(1))
+}
+
+/// Extract a zip archive under `dest` rejecting path traversal, symlinks,
+/// suspicious compression ratios and archives exceeding the given limits.
+///
+/// Every entry is checked before anything is written, and the number of
+/// bytes actually decompressed is enforced again while writing, in case
+/// the sizes declared in the archive are wrong.
+pub fn extract_zip<R: Read + Seek>(
+    reader: R,
+    dest: &Path,
+    options: &ExtractOptions,
+) -> Result<Vec<ExtractedFile>, ExtractError> {
+    let mut archive = ZipArchive::new(reader)?;
+    check_entries(&mut archive, options)?;
+
+    let mut extracted = Vec::new();
+    let mut remaining_bytes = options.max_total_bytes;
+    for i in 0..archive.len() {
+        let file = archive.by_index(i)?;
+        let name = match file.enclosed_name() {
+            Some(name) => name.to_path_buf(),
+            None => return Err(ExtractError::UnsafePath(file.name().to_string())),
+        };
+        let path = dest.join(&name);
+
+        if file.is_dir() {
+            fs::create_dir_all(&path)?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mode = file.unix_mode();
+        let mut outfile = File::create(&path)?;
+        let size = io::copy(&mut take_limit(file, remaining_bytes), &mut outfile)?;
+        if size > remaining_bytes {
+            drop(outfile);
+            fs::remove_file(&path)?;
+            return Err(ExtractError::TooLarge(options.max_total_bytes));
+        }
+        remaining_bytes -= size;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            if let (true, Some(mode)) = (options.preserve_permissions, mode) {
+                fs::set_permissions(&path, fs::Permissions::from_mode(mode & PERMISSIONS_MASK))?;
+            }
+        }
+
+        extracted.push(ExtractedFile {
+            name: name.to_string_lossy().to_string(),
+            path,
+            size,
+        });
+    }
+    Ok(extracted)
+}
+
+/// Extract the zip archive stored at `path` under `dest`, see [`extract_zip`].
+pub fn extract_zip_file(
+    path: &Path,
+    dest: &Path,
+    options: &ExtractOptions,
+) -> Result<Vec<ExtractedFile>, ExtractError> {
+    extract_zip(File::open(path)?, dest, options)
+}
+
+/// Read a single member of a zip archive into memory applying the same
+/// checks as [`extract_zip`].
+pub fn read_zip_member<R: Read + Seek>(
+    reader: R,
+    name: &str,
+    options: &ExtractOptions,
+) -> Result<Vec<u8>, ExtractError> {
+    let mut archive = ZipArchive::new(reader)?;
+    check_entries(&mut archive, options)?;
+
+    let file = archive.by_name(name)?;
+    let mut bytes = Vec::new();
+    take_limit(file, options.max_total_bytes).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 > options.max_total_bytes {
+        return Err(ExtractError::TooLarge(options.max_total_bytes));
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::FileOptions;
+    use zip::{CompressionMethod, ZipWriter};
+
+    fn zip_with(entries: &[(&str, &[u8])]) -> Cursor<Vec<u8>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in entries {
+            writer
+                .start_file(*name, FileOptions::default().unix_permissions(0o4755))
+                .unwrap();
+            writer.write_all(content).unwrap();
+        }
+        Cursor::new(writer.finish().unwrap().into_inner())
+    }
+
+    fn dest(name: &str) -> PathBuf {
+        let dest = std::env::temp_dir().join(format!("{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dest);
+        dest
+    }
+
+    #[test]
+    fn extract_zip_test() {
+        let dest = dest("mulligan_extract_test");
+        let zip = zip_with(&[
+            ("H41_data.xml", b"<data/>"),
+            ("dir/H41_struct.xml", b"<s/>"),
+        ]);
+        let options = ExtractOptions {
+            preserve_permissions: true,
+            ..ExtractOptions::default()
+        };
+
+        let extracted = extract_zip(zip, &dest, &options).unwrap();
+        assert_eq!(extracted.len(), 2);
+        assert_eq!(extracted[1].name, "dir/H41_struct.xml");
+        assert_eq!(fs::read(dest.join("H41_data.xml")).unwrap(), b"<data/>");
+        let zip = zip_with(&[("H41_data.xml", b"<data/>")]);
+        let member = read_zip_member(zip, "H41_data.xml", &options).unwrap();
+        assert_eq!(member, b"<data/>");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&extracted[0].path)
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o7777, 0o755);
+        }
+        fs::remove_dir_all(&dest).unwrap();
+    }
+
+    #[test]
+    fn extract_zip_unlimited_test() {
+        let dest = dest("mulligan_extract_unlimited_test");
+        let options = ExtractOptions {
+            max_total_bytes: u64::MAX,
+            max_compression_ratio: u64::MAX,
+            ..ExtractOptions::default()
+        };
+        let zip = zip_with(&[("H41_data.xml", b"<data/>")]);
+        let extracted = extract_zip(zip, &dest, &options).unwrap();
+        assert_eq!(extracted[0].size, 7);
+        assert_eq!(fs::read(dest.join("H41_data.xml")).unwrap(), b"<data/>");
+        let zip = zip_with(&[("H41_data.xml", b"<data/>")]);
+        let member = read_zip_member(zip, "H41_data.xml", &options).unwrap();
+        assert_eq!(member, b"<data/>");
+        fs::remove_dir_all(&dest).unwrap();
+    }
+
+    #[test]
+    fn extract_zip_rejects_unsafe_archives_test() {
+        let dest = dest("mulligan_extract_unsafe_test");
+        let options = ExtractOptions::default();
+
+        let zip = zip_with(&[("../evil.xml", b"x")]);
+        let result = extract_zip(zip, &dest, &options);
+        assert!(matches!(result, Err(ExtractError::UnsafePath(_))));
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .add_symlink("link", "/etc/passwd", FileOptions::default())
+            .unwrap();
+        let zip = Cursor::new(writer.finish().unwrap().into_inner());
+        let result = extract_zip(zip, &dest, &options);
+        assert!(matches!(result, Err(ExtractError::Symlink(_))));
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file(
+                "bomb.xml",
+                FileOptions::default().compression_method(CompressionMethod::Deflated),
+            )
+            .unwrap();
+        writer.write_all(&vec![0; 1 << 20]).unwrap();
+        let zip = Cursor::new(writer.finish().unwrap().into_inner());
+        let result = extract_zip(zip, &dest, &options);
+        assert!(matches!(result, Err(ExtractError::CompressionRatio(_))));
+
+        let zip = zip_with(&[("a.xml", b"12345"), ("b.xml", b"12345")]);
+        let small = ExtractOptions {
+            max_total_bytes: 8,
+            ..ExtractOptions::default()
+        };
+        assert!(matches!(
+            extract_zip(zip.clone(), &dest, &small),
+            Err(ExtractError::TooLarge(8))
+        ));
+        let few = ExtractOptions {
+            max_entries: 1,
+            ..ExtractOptions::default()
+        };
+        assert!(matches!(
+            extract_zip(zip, &dest, &few),
+            Err(ExtractError::TooManyEntries(2))
+        ));
+        assert!(!dest.exists());
+
+        let result = extract_zip(Cursor::new(b"not a zip".to_vec()), &dest, &options);
+        assert!(matches!(result, Err(ExtractError::Zip(_))));
+    }
+}
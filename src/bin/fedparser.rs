@@ -1,201 +1,1029 @@
-use clap::{arg, Command};
-use mulligan::fed;
-use mulligan::fed::ObservationMap;
-use mulligan::{Concept, ConceptType, NaiveDate};
+use clap::{arg, Arg, ArgGroup, ArgMatches, Command};
+use mulligan::archive::{self, ExtractOptions};
+use mulligan::cache::DownloadCache;
+use mulligan::change::{ChangePeriod, ObservationChanges};
+use mulligan::download::{FetchOptions, ReqwestClient};
+#[cfg(feature = "arrow")]
+use mulligan::export::arrow;
+use mulligan::export::csv::{self, CsvOptions};
+use mulligan::export::long;
+#[cfg(feature = "parquet")]
+use mulligan::export::parquet;
+#[cfg(feature = "xlsx")]
+use mulligan::export::xlsx::{self, WorkbookInfo};
+use mulligan::manifest::{Manifest, MANIFEST_FILE_NAME};
+use mulligan::resample::{self, Aggregation};
+use mulligan::residual;
+use mulligan::select::Selection;
+use mulligan::snapshot;
+use mulligan::source::{self, DataSource};
+#[cfg(feature = "sqlite")]
+use mulligan::store::{self, SqliteStore};
+use mulligan::validate::Tolerance;
+use mulligan::BalanceSheet;
+use mulligan::{ConceptType, NaiveDate, ObservationMap};
 use std::fs;
 use std::fs::File;
 use std::io;
-use std::io::Write;
+use std::io::{BufWriter, Write};
 use std::path::Path;
 use std::str;
 
-const H41_FILE_PATH: &str = "/tmp/h41.zip";
+const DEFAULT_CACHE_DIR: &str = "/tmp/mulligan";
+const DEFAULT_SOURCE: &str = "h41";
+const DEFAULT_OUTPUT_DIR: &str = "./tmp";
+const DATE_FORMAT: &str = "%Y-%m-%d";
+const ZIP_EXTENSION: &str = ".zip";
+const JSON_EXTENSION: &str = ".json";
+const SNAPSHOT_EXTENSION: &str = ".snapshot";
+const JSON_FORMAT: &str = "json";
+const CSV_FORMAT: &str = "csv";
+const SNAPSHOT_FORMAT: &str = "snapshot";
+const LONG_CSV_FORMAT: &str = "long-csv";
+const JSONL_FORMAT: &str = "jsonl";
+const PARQUET_FORMAT: &str = "parquet";
+const LONG_PARQUET_FORMAT: &str = "long-parquet";
+const ARROW_FORMAT: &str = "arrow";
+const LONG_ARROW_FORMAT: &str = "long-arrow";
+const XLSX_FORMAT: &str = "xlsx";
 const OBS_JSON_FILE_NAME: &str = "observations.json";
+const SNAPSHOT_FILE_NAME: &str = "observations.snapshot";
 const ASSETS_CSV_FILE_NAME: &str = "assets.csv";
 const LIABILITIES_CSV_FILE_NAME: &str = "liabilities.csv";
 const CAPITAL_CSV_FILE_NAME: &str = "capital.csv";
-const CSV_SEPARATOR_STR: &str = ",";
+const LONG_CSV_FILE_NAME: &str = "observations_long.csv";
+const JSONL_FILE_NAME: &str = "observations.jsonl";
+#[cfg(feature = "parquet")]
+const ASSETS_PARQUET_FILE_NAME: &str = "assets.parquet";
+#[cfg(feature = "parquet")]
+const LIABILITIES_PARQUET_FILE_NAME: &str = "liabilities.parquet";
+#[cfg(feature = "parquet")]
+const CAPITAL_PARQUET_FILE_NAME: &str = "capital.parquet";
+#[cfg(feature = "parquet")]
+const LONG_PARQUET_FILE_NAME: &str = "observations_long.parquet";
+#[cfg(feature = "xlsx")]
+const XLSX_FILE_NAME: &str = "observations.xlsx";
+#[cfg(feature = "arrow")]
+const ASSETS_ARROW_FILE_NAME: &str = "assets.arrow";
+#[cfg(feature = "arrow")]
+const LIABILITIES_ARROW_FILE_NAME: &str = "liabilities.arrow";
+#[cfg(feature = "arrow")]
+const CAPITAL_ARROW_FILE_NAME: &str = "capital.arrow";
+#[cfg(feature = "arrow")]
+const LONG_ARROW_FILE_NAME: &str = "observations_long.arrow";
+
+fn extract_zipfile(
+    file_path: &Path,
+    prepend_path: &str,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let extracted = archive::extract_zip_file(
+        file_path,
+        Path::new(prepend_path),
+        &ExtractOptions::default(),
+    )?;
+    for (i, file) in extracted.iter().enumerate() {
+        println!(
+            "File {i} extracted to \"{}\" ({} bytes)",
+            file.path.display(),
+            file.size
+        );
+    }
+    Ok(extracted.into_iter().map(|f| f.name).collect())
+}
+
+fn path_exists(path: &str) -> bool {
+    fs::metadata(path).is_ok()
+}
+
+fn create_observation_json_file(
+    dst_path: &str,
+    obs: &ObservationMap,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let json = serde_json::to_string_pretty(&obs).unwrap();
 
-#[tokio::main]
-async fn download_file(target: &str, dst_path: &str) -> Result<(), Box<dyn std::error::Error>> {
     let mut dest = {
-        println!("Temporary file will be located under: '{dst_path:?}'");
+        println!("observations json file will be located under: '{dst_path:?}'");
         File::create(dst_path)?
     };
 
-    let response = reqwest::get(target).await?;
-    let bytes = response.bytes().await?;
-    let mut data: &[u8] = bytes.as_ref();
-    io::copy(&mut data, &mut dest)?;
-    println!("Downloaded temporal file: '{dst_path:?}'");
+    io::copy(&mut json.as_bytes(), &mut dest)?;
+    println!("observations json file created");
 
     Ok(())
 }
 
-fn extract_zipfile(file_path: &str, prepend_path: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let file = File::open(file_path).unwrap();
+fn csv_file_name(ctype: &ConceptType) -> &'static str {
+    match ctype {
+        ConceptType::Assets => ASSETS_CSV_FILE_NAME,
+        ConceptType::Liabilities => LIABILITIES_CSV_FILE_NAME,
+        ConceptType::Capital => CAPITAL_CSV_FILE_NAME,
+    }
+}
 
-    let mut archive = zip::ZipArchive::new(file).unwrap();
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i).unwrap();
-        let outpath = Path::new(prepend_path).join(file.mangled_name());
+fn create_observation_csv_file(
+    dst_path: &str,
+    obs: &ObservationMap,
+    ctype: &ConceptType,
+    options: &CsvOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut dest = {
+        println!("observations csv file will be located under: '{dst_path:?}'");
+        BufWriter::new(File::create(dst_path)?)
+    };
 
-        {
-            let comment = file.comment();
-            if !comment.is_empty() {
-                println!("File {i} comment: {comment}");
-            }
-        }
+    csv::write_csv(&mut dest, obs, ctype, options)?;
+    dest.flush()?;
 
-        if (*file.name()).ends_with('/') {
-            println!("File {i} extracted to \"{}\"", outpath.as_path().display());
-            fs::create_dir_all(&outpath).unwrap();
-        } else {
-            println!(
-                "File {i} extracted to \"{}\" ({} bytes)",
-                outpath.as_path().display(),
-                file.size()
-            );
-            if let Some(p) = outpath.parent() {
-                if !p.exists() {
-                    fs::create_dir_all(p).unwrap();
-                }
-            }
-            let mut outfile = fs::File::create(&outpath).unwrap();
-            io::copy(&mut file, &mut outfile).unwrap();
-        }
+    println!("observations csv file created");
 
-        // Get and Set permissions
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
+    Ok(())
+}
 
-            if let Some(mode) = file.unix_mode() {
-                fs::set_permissions(&outpath, fs::Permissions::from_mode(mode)).unwrap();
-            }
-        }
+fn create_observation_long_file(
+    dst_path: &str,
+    obs: &ObservationMap,
+    statements: &[ConceptType],
+    format: &str,
+    options: &CsvOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut dest = {
+        println!("observations {format} file will be located under: '{dst_path:?}'");
+        BufWriter::new(File::create(dst_path)?)
+    };
+
+    if format == JSONL_FORMAT {
+        long::write_json_lines(&mut dest, obs, statements)?;
+    } else {
+        long::write_long_csv(&mut dest, obs, statements, options)?;
     }
+    dest.flush()?;
+
+    println!("observations {format} file created");
+
     Ok(())
 }
 
-fn path_exists(path: &str) -> bool {
-    fs::metadata(path).is_ok()
+#[cfg(feature = "parquet")]
+fn parquet_file_name(ctype: &ConceptType) -> &'static str {
+    match ctype {
+        ConceptType::Assets => ASSETS_PARQUET_FILE_NAME,
+        ConceptType::Liabilities => LIABILITIES_PARQUET_FILE_NAME,
+        ConceptType::Capital => CAPITAL_PARQUET_FILE_NAME,
+    }
 }
 
-fn create_observation_json_file(
+#[cfg(feature = "parquet")]
+fn create_observation_parquet_file(
     dst_path: &str,
     obs: &ObservationMap,
+    statements: &[ConceptType],
+    long: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let json = serde_json::to_string_pretty(&obs).unwrap();
+    let dest = {
+        println!("observations parquet file will be located under: '{dst_path:?}'");
+        BufWriter::new(File::create(dst_path)?)
+    };
+
+    if long {
+        parquet::write_long_parquet(dest, obs, statements)?;
+    } else {
+        parquet::write_parquet(dest, obs, &statements[0])?;
+    }
+
+    println!("observations parquet file created");
 
+    Ok(())
+}
+
+#[cfg(feature = "arrow")]
+fn arrow_file_name(ctype: &ConceptType) -> &'static str {
+    match ctype {
+        ConceptType::Assets => ASSETS_ARROW_FILE_NAME,
+        ConceptType::Liabilities => LIABILITIES_ARROW_FILE_NAME,
+        ConceptType::Capital => CAPITAL_ARROW_FILE_NAME,
+    }
+}
+
+#[cfg(feature = "arrow")]
+fn create_observation_arrow_file(
+    dst_path: &str,
+    obs: &ObservationMap,
+    statements: &[ConceptType],
+    long: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let batch = if long {
+        arrow::to_long_record_batch(obs, statements)?
+    } else {
+        arrow::to_record_batch(obs, &statements[0])?
+    };
     let mut dest = {
-        println!("observations json file will be located under: '{dst_path:?}'");
-        File::create(dst_path)?
+        println!("observations arrow file will be located under: '{dst_path:?}'");
+        BufWriter::new(File::create(dst_path)?)
     };
 
-    io::copy(&mut json.as_bytes(), &mut dest)?;
-    println!("observations json file created");
+    arrow::write_ipc(&mut dest, &[batch])?;
+    dest.flush()?;
+
+    println!("observations arrow file created");
 
     Ok(())
 }
 
-fn csv_header(c: &Concept) -> Result<String, Box<dyn std::error::Error>> {
-    let fields: Vec<String> = c
-        .iter()
-        .filter(|v| v.is_leaf())
-        .map(|s| {
-            let name = s.name();
-            if name.contains(CSV_SEPARATOR_STR) {
-                format!("\"{name}\"")
-            } else {
-                name.to_string()
-            }
-        })
-        .collect();
+/// Write the observations as a workbook, taking the release information
+/// from the manifest of the output directory if there is one.
+#[cfg(feature = "xlsx")]
+fn create_observation_xlsx_file(
+    dst_path: &str,
+    obs: &ObservationMap,
+    statements: &[ConceptType],
+    source: &dyn DataSource,
+    output_dir: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let manifest = Manifest::read(Path::new(output_dir)).ok();
+    let info = WorkbookInfo {
+        source: source.id().to_string(),
+        description: source.description().to_string(),
+        url: manifest
+            .as_ref()
+            .map_or_else(|| source.urls()[0].to_string(), |m| m.url.clone()),
+        prepared: manifest.and_then(|m| m.prepared),
+    };
+    let mut dest = {
+        println!("observations xlsx file will be located under: '{dst_path:?}'");
+        BufWriter::new(File::create(dst_path)?)
+    };
+
+    xlsx::write_xlsx(&mut dest, obs, statements, &info)?;
+    dest.flush()?;
 
-    Ok(fields.join(CSV_SEPARATOR_STR))
+    println!("observations xlsx file created");
+
+    Ok(())
 }
 
-fn csv_row(date: &NaiveDate, c: &Concept) -> Result<String, Box<dyn std::error::Error>> {
-    let row_date = date.to_string();
-    let row_values: Vec<String> = c
+fn list_sources() {
+    for source in source::sources() {
+        let statements: Vec<String> = source.statements().iter().map(|s| s.to_string()).collect();
+        println!(
+            "{}: {} [{}]\n  {}",
+            source.id(),
+            source.description(),
+            statements.join(", "),
+            source.urls().join("\n  ")
+        );
+    }
+}
+
+fn unpack_download(
+    source: &dyn DataSource,
+    download_path: &Path,
+    output_dir: &str,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    if source.archive_members().is_empty() {
+        fs::create_dir_all(output_dir)?;
+        fs::copy(
+            download_path,
+            format!("{output_dir}/{}", source.data_file()),
+        )?;
+        Ok(vec![source.data_file().to_string()])
+    } else {
+        extract_zipfile(download_path, output_dir)
+    }
+}
+
+/// Vintage of an input file: the time its publisher prepared the data
+/// file, read from its header or, for exported observations, from the
+/// manifest next to them.
+#[cfg(feature = "sqlite")]
+fn vintage_of(source: &dyn DataSource, input: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let prepared = if input.ends_with(JSON_EXTENSION) || input.ends_with(SNAPSHOT_EXTENSION) {
+        let dir = Path::new(input).parent().unwrap_or_else(|| Path::new("."));
+        Manifest::read(dir)
+            .ok()
+            .and_then(|manifest| manifest.prepared)
+    } else {
+        source.prepared(&read_data_file(source, input)?)
+    };
+    prepared.ok_or_else(|| format!("No prepared time found for {input}, set --vintage").into())
+}
+
+#[cfg(feature = "sqlite")]
+fn store_observations(
+    source: &dyn DataSource,
+    input: &str,
+    db: &str,
+    vintage: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let observations = load_observations(source, input)?;
+    let vintage = match vintage {
+        Some(vintage) => vintage.to_string(),
+        None => vintage_of(source, input)?,
+    };
+    let vintage = store::normalize_vintage(&vintage)?;
+    let mut store = SqliteStore::open(Path::new(db))?;
+    store.upsert(source.id(), &vintage, &observations)?;
+    println!(
+        "{} balance sheets of {} stored in {db} as vintage {vintage}",
+        observations.len(),
+        source.id()
+    );
+    Ok(())
+}
+
+fn verify_output_dir(
+    output_dir: &str,
+    archive: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let manifest = Manifest::read(Path::new(output_dir))?;
+    println!(
+        "Verifying {output_dir} built by mulligan {} from {} downloaded at {}",
+        manifest.mulligan_version, manifest.url, manifest.downloaded_at
+    );
+    let mut mismatches: Vec<String> = manifest
+        .verify(Path::new(output_dir))
         .iter()
-        .filter(|v| v.is_leaf())
-        .map(|v| v.value.to_string())
+        .map(|m| m.to_string())
         .collect();
+    if let Some(archive) = archive {
+        if !manifest.verify_download(Path::new(archive))? {
+            mismatches.push(format!("{archive}: expected sha256 {}", manifest.sha256));
+        }
+    }
 
-    Ok(format!(
-        "{}{}{}\n",
-        row_date,
-        CSV_SEPARATOR_STR,
-        row_values.join(CSV_SEPARATOR_STR)
-    ))
+    if mismatches.is_empty() {
+        println!("{} members verified", manifest.members.len());
+        Ok(())
+    } else {
+        for mismatch in &mismatches {
+            println!("{mismatch}");
+        }
+        Err(format!("{} mismatches found in {output_dir}", mismatches.len()).into())
+    }
 }
 
-fn create_observation_csv_file(
-    dst_path: &str,
-    obs: &ObservationMap,
-    ctype: &ConceptType,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let header = csv_header(obs.iter().next().unwrap().1.get_concept(ctype))?;
+/// Download the source through the cache and unpack it into the output
+/// directory, returns false if the download did not change since last run
+/// and the output directory was already built from it.
+fn fetch_source(
+    source: &dyn DataSource,
+    output_dir: &str,
+    cache_dir: &str,
+    force: bool,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let cache = DownloadCache::new(cache_dir);
+    let download = cache.fetch(
+        &ReqwestClient::default(),
+        source.urls()[0],
+        &format!("{}.download", source.id()),
+        &FetchOptions::default(),
+    )?;
+    println!("Downloaded file cached under: '{:?}'", download.path);
 
-    let mut dest = {
-        println!("observations csv file will be located under: '{dst_path:?}'");
-        File::create(dst_path)?
-    };
+    let manifest_file = format!("{output_dir}/{MANIFEST_FILE_NAME}");
+    if !download.changed && !force && path_exists(&manifest_file) {
+        println!("Download did not change since last run, skip unpacking");
+        return Ok(false);
+    }
+    let members = unpack_download(source, &download.path, output_dir)?;
+    let manifest = Manifest::new(source, &download, Path::new(output_dir), &members)?;
+    manifest.write(Path::new(output_dir))?;
+    println!("Manifest file written under: '{manifest_file}'");
+
+    Ok(true)
+}
 
-    dest.write_all(b"date")?;
-    dest.write_all(CSV_SEPARATOR_STR.as_bytes())?;
-    dest.write_all(header.as_bytes())?;
-    dest.write_all(b"\n")?;
-    for (obs_date, obs_balance_sheet) in obs {
-        let row = csv_row(obs_date, obs_balance_sheet.get_concept(ctype))?;
-        io::copy(&mut row.as_bytes(), &mut dest)?;
+/// Load observations from a zip archive as downloaded from the source,
+/// a data file of the source or a previously written observations json file.
+fn read_data_file(
+    source: &dyn DataSource,
+    input: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    if input.ends_with(ZIP_EXTENSION) {
+        let bytes = archive::read_zip_member(
+            File::open(input)?,
+            source.data_file(),
+            &ExtractOptions::default(),
+        )?;
+        Ok(String::from_utf8(bytes)?)
+    } else {
+        Ok(fs::read_to_string(input)?)
     }
+}
 
-    println!("observations csv file created");
+fn load_observations(
+    source: &dyn DataSource,
+    input: &str,
+) -> Result<ObservationMap, Box<dyn std::error::Error>> {
+    if input.ends_with(JSON_EXTENSION) {
+        return Ok(serde_json::from_str(&fs::read_to_string(input)?)?);
+    }
+    if input.ends_with(SNAPSHOT_EXTENSION) {
+        return snapshot::load(Path::new(input));
+    }
+    source.parse(&read_data_file(source, input)?)
+}
+
+#[cfg_attr(not(feature = "xlsx"), allow(unused_variables))]
+fn export_observations(
+    source: &dyn DataSource,
+    observations: &ObservationMap,
+    output_dir: &str,
+    formats: &[&str],
+    statements: &[ConceptType],
+    csv_options: &CsvOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    fs::create_dir_all(output_dir)?;
+    for format in formats {
+        match *format {
+            JSON_FORMAT => {
+                let obs_json_file = format!("{output_dir}/{OBS_JSON_FILE_NAME}");
+                create_observation_json_file(&obs_json_file, observations)?;
+            }
+            SNAPSHOT_FORMAT => {
+                let snapshot_file = format!("{output_dir}/{SNAPSHOT_FILE_NAME}");
+                println!("observations snapshot will be located under: '{snapshot_file:?}'");
+                snapshot::save(Path::new(&snapshot_file), observations)?;
+                println!("observations snapshot created");
+            }
+            CSV_FORMAT => {
+                for ctype in statements {
+                    let csv_file = format!("{output_dir}/{}", csv_file_name(ctype));
+                    create_observation_csv_file(&csv_file, observations, ctype, csv_options)?;
+                }
+            }
+            LONG_CSV_FORMAT => {
+                let long_file = format!("{output_dir}/{LONG_CSV_FILE_NAME}");
+                create_observation_long_file(
+                    &long_file,
+                    observations,
+                    statements,
+                    format,
+                    csv_options,
+                )?;
+            }
+            JSONL_FORMAT => {
+                let jsonl_file = format!("{output_dir}/{JSONL_FILE_NAME}");
+                create_observation_long_file(
+                    &jsonl_file,
+                    observations,
+                    statements,
+                    format,
+                    csv_options,
+                )?;
+            }
+            #[cfg(feature = "parquet")]
+            PARQUET_FORMAT => {
+                for ctype in statements {
+                    let parquet_file = format!("{output_dir}/{}", parquet_file_name(ctype));
+                    create_observation_parquet_file(&parquet_file, observations, &[*ctype], false)?;
+                }
+            }
+            #[cfg(feature = "parquet")]
+            LONG_PARQUET_FORMAT => {
+                let parquet_file = format!("{output_dir}/{LONG_PARQUET_FILE_NAME}");
+                create_observation_parquet_file(&parquet_file, observations, statements, true)?;
+            }
+            #[cfg(not(feature = "parquet"))]
+            PARQUET_FORMAT | LONG_PARQUET_FORMAT => {
+                return Err(
+                    format!("{format} export requires building with the parquet feature").into(),
+                )
+            }
+            #[cfg(feature = "arrow")]
+            ARROW_FORMAT => {
+                for ctype in statements {
+                    let arrow_file = format!("{output_dir}/{}", arrow_file_name(ctype));
+                    create_observation_arrow_file(&arrow_file, observations, &[*ctype], false)?;
+                }
+            }
+            #[cfg(feature = "arrow")]
+            LONG_ARROW_FORMAT => {
+                let arrow_file = format!("{output_dir}/{LONG_ARROW_FILE_NAME}");
+                create_observation_arrow_file(&arrow_file, observations, statements, true)?;
+            }
+            #[cfg(feature = "xlsx")]
+            XLSX_FORMAT => {
+                let xlsx_file = format!("{output_dir}/{XLSX_FILE_NAME}");
+                create_observation_xlsx_file(
+                    &xlsx_file,
+                    observations,
+                    statements,
+                    source,
+                    output_dir,
+                )?;
+            }
+            #[cfg(not(feature = "xlsx"))]
+            XLSX_FORMAT => {
+                return Err(
+                    format!("{format} export requires building with the xlsx feature").into(),
+                )
+            }
+            #[cfg(not(feature = "arrow"))]
+            ARROW_FORMAT | LONG_ARROW_FORMAT => {
+                return Err(
+                    format!("{format} export requires building with the arrow feature").into(),
+                )
+            }
+            _ => return Err(format!("Unknown export format: {format}").into()),
+        }
+    }
+    Ok(())
+}
 
+fn balance_sheet_at<'a>(
+    observations: &'a ObservationMap,
+    date: Option<&str>,
+) -> Result<(&'a NaiveDate, &'a BalanceSheet), Box<dyn std::error::Error>> {
+    match date {
+        Some(date) => {
+            let date = NaiveDate::parse_from_str(date, DATE_FORMAT)?;
+            observations
+                .get_key_value(&date)
+                .ok_or_else(|| format!("No observations for date {date}").into())
+        }
+        None => observations
+            .iter()
+            .next_back()
+            .ok_or_else(|| "No observations found".into()),
+    }
+}
+
+fn show_balance_sheet(
+    observations: &ObservationMap,
+    date: Option<&str>,
+    statements: &[ConceptType],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (date, bs) = balance_sheet_at(observations, date)?;
+    println!("Balance sheet {date}");
+    for ctype in statements {
+        println!("{}", bs.get_concept(ctype));
+    }
     Ok(())
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let matches = Command::new("fedparser")
-        .version("0.2")
-        .author("Jorge Perez Burgos <vaijira@gmail.com>")
-        .about("Download H41 data from federal reserve website.")
-        .arg(arg!(-o --output [OUTPUT_DIR] "Sets the output directory, default: /tmp"))
-        .get_matches();
+type DatedBalanceSheet<'a> = (&'a NaiveDate, &'a BalanceSheet);
 
-    // Gets a value for config if supplied by user, or defaults to "default.conf"
-    let output_dir = matches.value_of("OUTPUT_DIR").unwrap_or("./tmp");
-    println!("Value for output dir: {output_dir}");
+fn compared_balance_sheets<'a>(
+    observations: &'a ObservationMap,
+    from: Option<&str>,
+    to: Option<&str>,
+    period: Option<&str>,
+) -> Result<(DatedBalanceSheet<'a>, DatedBalanceSheet<'a>), Box<dyn std::error::Error>> {
+    let to = balance_sheet_at(observations, to)?;
+    let from = match period {
+        Some(period) => {
+            let period: ChangePeriod = period.parse()?;
+            let date = observations
+                .base_date(to.0, period)
+                .ok_or(format!("No balance sheet {period} before {}", to.0))?;
+            observations.get_key_value(&date).unwrap()
+        }
+        None => balance_sheet_at(observations, from)?,
+    };
+    Ok((from, to))
+}
+
+fn diff_balance_sheets(
+    observations: &ObservationMap,
+    from: Option<&str>,
+    to: Option<&str>,
+    period: Option<&str>,
+    statements: &[ConceptType],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let ((from_date, from_bs), (to_date, to_bs)) =
+        compared_balance_sheets(observations, from, to, period)?;
+    let change = from_bs.diff(to_bs);
+    println!(
+        "{:<68.68}{:>12}{:>12}{:>12}{:>10}",
+        "Balance sheet",
+        from_date.to_string(),
+        to_date.to_string(),
+        "Change",
+        "%"
+    );
+    for ctype in statements {
+        for c in change.get_concept(ctype).iter() {
+            println!(
+                "{:<68.68}{:>12}{:>12}{:>12}{:>10}",
+                " ".repeat(c.depth() * 2) + c.name(),
+                c.from,
+                c.to,
+                c.change,
+                c.percent.map_or(String::new(), |p| format!("{p:.2}"))
+            );
+        }
+    }
+    Ok(())
+}
 
-    if path_exists(output_dir) {
-        println!("Directory {output_dir} already exists, skip downloading");
+fn explain_change(
+    observations: &ObservationMap,
+    concept: &str,
+    from: Option<&str>,
+    to: Option<&str>,
+    period: Option<&str>,
+    children: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let ((from_date, from_bs), (to_date, to_bs)) =
+        compared_balance_sheets(observations, from, to, period)?;
+    // Intermediate concepts without series only change once rolled up.
+    let (mut from_bs, mut to_bs) = (from_bs.clone(), to_bs.clone());
+    from_bs.rollup();
+    to_bs.rollup();
+    let change = from_bs.diff(&to_bs);
+    let total = change
+        .get(concept)
+        .ok_or(format!("Unknown concept: {concept}"))?;
+    println!(
+        "{concept} changed {} from {from_date} to {to_date}",
+        total.change
+    );
+    println!("{:<80.80}{:>12}{:>10}", "Concept", "Change", "Share %");
+    let contributions = if children {
+        total.contributions()
     } else {
-        download_file(mulligan::fed::H41_FED_URL, H41_FILE_PATH)?;
-        extract_zipfile(H41_FILE_PATH, output_dir)?;
+        total.leaf_contributions()
+    };
+    for c in contributions {
+        println!(
+            "{:<80.80}{:>12}{:>10}",
+            c.concept.path,
+            c.concept.change,
+            c.share.map_or(String::new(), |s| format!("{s:.2}"))
+        );
     }
+    Ok(())
+}
+
+fn source_arg() -> Arg<'static> {
+    arg!(-s --source [SOURCE] "Sets the data source to process, default: h41")
+}
+
+fn output_arg() -> Arg<'static> {
+    arg!(-o --output [OUTPUT_DIR] "Sets the output directory, default: ./tmp")
+}
 
-    let h41_data_file = format!("{output_dir}/{}", fed::H41_DATA_XML);
-    let h41_data_text = std::fs::read_to_string(h41_data_file)?;
-    let observations = fed::parse_h41_data(&h41_data_text)?;
+fn cache_arg() -> Arg<'static> {
+    arg!(-c --cache [CACHE_DIR] "Sets the download cache directory, default: /tmp/mulligan")
+}
 
-    let obs_json_file = format!("{output_dir}/{OBS_JSON_FILE_NAME}");
-    create_observation_json_file(&obs_json_file, &observations)?;
+fn force_arg() -> Arg<'static> {
+    arg!(-f --force "Processes the data even if the download did not change")
+}
 
-    let assets_csv_file = format!("{output_dir}/{ASSETS_CSV_FILE_NAME}");
-    create_observation_csv_file(&assets_csv_file, &observations, &ConceptType::Assets)?;
+fn input_arg() -> Arg<'static> {
+    arg!(-i --input <INPUT> "Sets the input zip archive, data file, observations json or snapshot file")
+}
 
-    let liabilities_csv_file = format!("{output_dir}/{LIABILITIES_CSV_FILE_NAME}");
-    create_observation_csv_file(
-        &liabilities_csv_file,
+fn totals_args() -> [Arg<'static>; 2] {
+    [
+        arg!(--rollup "Fills intermediate concepts without series with the sum of their children"),
+        arg!(--residuals "Adds a leaf with the unallocated difference under published totals"),
+    ]
+}
+
+fn resample_args() -> [Arg<'static>; 2] {
+    [
+        arg!(--frequency [FREQUENCY] "Resamples the observations to calendar periods (monthly, quarterly, annual)"),
+        arg!(--aggregation [AGGREGATION] "Sets how resampled periods are aggregated (end, mean, max, min), default: end")
+            .requires("frequency"),
+    ]
+}
+
+fn list_arg(name: &'static str, help: &'static str) -> Arg<'static> {
+    Arg::new(name)
+        .long(name)
+        .value_name(name)
+        .help(help)
+        .takes_value(true)
+        .use_value_delimiter(true)
+}
+
+fn statements_arg() -> Arg<'static> {
+    list_arg(
+        "statements",
+        "Sets the comma separated statements to process, default: all the source ones",
+    )
+}
+
+fn selection_args() -> [Arg<'static>; 5] {
+    [
+        arg!(--from [FROM] "Sets the first date exported (YYYY-MM-DD)"),
+        arg!(--to [TO] "Sets the last date exported (YYYY-MM-DD)"),
+        list_arg(
+            "dates",
+            "Sets the comma separated dates exported (YYYY-MM-DD)",
+        ),
+        arg!(--concept [CONCEPT] "Sets the path of the concept exported with its children"),
+        arg!(--depth [DEPTH] "Sets the maximum depth of the concepts exported"),
+    ]
+}
+
+fn selection_from(matches: &ArgMatches) -> Result<Selection, Box<dyn std::error::Error>> {
+    let date = |name: &str| {
+        matches
+            .value_of(name)
+            .map(|d| NaiveDate::parse_from_str(d, DATE_FORMAT))
+            .transpose()
+    };
+    Ok(Selection {
+        from: date("from")?,
+        to: date("to")?,
+        dates: matches
+            .values_of("dates")
+            .map(|dates| {
+                dates
+                    .map(|d| NaiveDate::parse_from_str(d, DATE_FORMAT))
+                    .collect()
+            })
+            .transpose()?,
+        concept: matches.value_of("concept").map(|c| c.to_string()),
+        depth: matches.value_of("depth").map(|d| d.parse()).transpose()?,
+    })
+}
+
+fn csv_args() -> [Arg<'static>; 3] {
+    [
+        arg!(--"csv-header" [CSV_HEADER] "Sets the CSV concept headers (name, path, series), default: name"),
+        arg!(--delimiter [DELIMITER] "Sets the CSV delimiter, default: ,"),
+        arg!(--crlf "Terminates the CSV records with CRLF instead of LF"),
+    ]
+}
+
+fn csv_options_from(matches: &ArgMatches) -> Result<CsvOptions, Box<dyn std::error::Error>> {
+    let mut options = CsvOptions {
+        crlf: matches.is_present("crlf"),
+        ..CsvOptions::default()
+    };
+    if let Some(header) = matches.value_of("csv-header") {
+        options.header = header.parse()?;
+    }
+    if let Some(delimiter) = matches.value_of("delimiter") {
+        let mut chars = delimiter.chars();
+        options.delimiter = match (chars.next(), chars.next()) {
+            (Some(c), None) => c,
+            _ => return Err(format!("Invalid CSV delimiter: {delimiter}").into()),
+        };
+        csv::check_delimiter(options.delimiter)?;
+    }
+    Ok(options)
+}
+
+fn totals_from(matches: &ArgMatches, observations: &mut ObservationMap) {
+    if matches.is_present("residuals") {
+        residual::add_residuals(observations, &Tolerance::default());
+    }
+    if matches.is_present("rollup") {
+        for bs in observations.values_mut() {
+            bs.rollup();
+        }
+    }
+}
+
+fn resample_from(
+    matches: &ArgMatches,
+    observations: ObservationMap,
+) -> Result<ObservationMap, Box<dyn std::error::Error>> {
+    let Some(frequency) = matches.value_of("frequency") else {
+        return Ok(observations);
+    };
+    let aggregation = match matches.value_of("aggregation") {
+        Some(aggregation) => aggregation.parse()?,
+        None => Aggregation::default(),
+    };
+    Ok(resample::resample(
         &observations,
-        &ConceptType::Liabilities,
-    )?;
+        frequency.parse()?,
+        aggregation,
+    ))
+}
 
-    let capital_csv_file = format!("{output_dir}/{CAPITAL_CSV_FILE_NAME}");
-    create_observation_csv_file(&capital_csv_file, &observations, &ConceptType::Capital)?;
+fn source_from(matches: &ArgMatches) -> Result<&'static dyn DataSource, String> {
+    let source_id = matches.value_of("source").unwrap_or(DEFAULT_SOURCE);
+    source::find_source(source_id).ok_or(format!("Unknown data source: {source_id}"))
+}
 
-    Ok(())
+fn statements_from(
+    matches: &ArgMatches,
+    source: &dyn DataSource,
+) -> Result<Vec<ConceptType>, String> {
+    match matches.values_of("statements") {
+        Some(values) => values.map(|v| v.parse()).collect(),
+        None => Ok(source.statements().to_vec()),
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let app = Command::new("fedparser")
+        .version("0.2")
+        .author("Jorge Perez Burgos <vaijira@gmail.com>")
+        .about("Download H41 and other central bank balance sheet data.")
+        .args_conflicts_with_subcommands(true)
+        .arg(output_arg())
+        .arg(source_arg())
+        .arg(cache_arg())
+        .arg(force_arg())
+        .subcommand(Command::new("sources").about("List the supported data sources."))
+        .subcommand(
+            Command::new("fetch")
+                .about("Download a data source and unpack it into the output directory.")
+                .arg(output_arg())
+                .arg(source_arg())
+                .arg(cache_arg())
+                .arg(force_arg()),
+        )
+        .subcommand(
+            Command::new("parse")
+                .about("Parse a local zip archive or data file into observations json.")
+                .arg(input_arg())
+                .arg(output_arg())
+                .arg(source_arg())
+                .args(totals_args())
+                .args(resample_args())
+                .args(selection_args()),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Export observations to the chosen formats.")
+                .arg(input_arg())
+                .arg(output_arg())
+                .arg(source_arg())
+                .args(totals_args())
+                .args(resample_args())
+                .arg(statements_arg())
+                .args(selection_args())
+                .args(csv_args())
+                .arg(list_arg(
+                    "formats",
+                    "Sets the comma separated export formats (json, csv, snapshot, long-csv, jsonl, parquet, long-parquet, arrow, long-arrow, xlsx), default: json,csv",
+                )
+                .visible_alias("format")),
+        )
+        .subcommand(
+            Command::new("show")
+                .about("Print the balance sheet of a date.")
+                .arg(input_arg())
+                .arg(source_arg())
+                .args(totals_args())
+                .arg(statements_arg())
+                .arg(arg!(-d --date [DATE] "Sets the date (YYYY-MM-DD), default: latest")),
+        )
+        .subcommand(
+            Command::new("diff")
+                .about("Compare the balance sheets of two dates.")
+                .arg(input_arg())
+                .arg(source_arg())
+                .args(totals_args())
+                .arg(statements_arg())
+                .arg(arg!(--from [FROM] "Sets the first date (YYYY-MM-DD)"))
+                .arg(arg!(--to [TO] "Sets the second date (YYYY-MM-DD), default: latest"))
+                .arg(arg!(--period [PERIOD] "Sets the change period instead of the first date (1w, 4w, 13w, 1y)"))
+                .group(ArgGroup::new("base").args(&["from", "period"]).required(true)),
+        )
+        .subcommand(
+            Command::new("explain")
+                .about("Rank the leaves below a concept by their contribution to its change.")
+                .arg(input_arg())
+                .arg(source_arg())
+                .args(totals_args())
+                .arg(arg!(-c --concept <CONCEPT> "Sets the concept path to explain, e.g. Assets"))
+                .arg(arg!(--from [FROM] "Sets the first date (YYYY-MM-DD)"))
+                .arg(arg!(--to [TO] "Sets the second date (YYYY-MM-DD), default: latest"))
+                .arg(arg!(--period [PERIOD] "Sets the change period instead of the first date (1w, 4w, 13w, 1y)"))
+                .arg(arg!(--children "Ranks the children of the concept instead of the leaves below it"))
+                .group(ArgGroup::new("base").args(&["from", "period"]).required(true)),
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("Verify an output directory against its manifest.")
+                .arg(output_arg())
+                .arg(arg!(-a --archive [ARCHIVE] "Also verifies the downloaded archive")),
+        );
+    #[cfg(feature = "sqlite")]
+    let app = app.subcommand(
+        Command::new("store")
+            .about("Insert or update observations in a SQLite database.")
+            .arg(input_arg())
+            .arg(source_arg())
+            .arg(arg!(--db <DB> "Sets the SQLite database file"))
+            .arg(arg!(--vintage [VINTAGE] "Sets the release vintage (RFC 3339 time or YYYY-MM-DD), default: prepared time of the data")),
+    );
+    let matches = app.get_matches();
+
+    match matches.subcommand() {
+        Some(("sources", _)) => {
+            list_sources();
+            Ok(())
+        }
+        Some(("fetch", m)) => {
+            fetch_source(
+                source_from(m)?,
+                m.value_of("output").unwrap_or(DEFAULT_OUTPUT_DIR),
+                m.value_of("cache").unwrap_or(DEFAULT_CACHE_DIR),
+                m.is_present("force"),
+            )?;
+            Ok(())
+        }
+        Some(("parse", m)) => {
+            let source = source_from(m)?;
+            let mut observations = load_observations(source, m.value_of("input").unwrap())?;
+            totals_from(m, &mut observations);
+            let observations = resample_from(m, observations)?;
+            let observations = selection_from(m)?.apply(&observations)?;
+            let output_dir = m.value_of("output").unwrap_or(DEFAULT_OUTPUT_DIR);
+            export_observations(
+                source,
+                &observations,
+                output_dir,
+                &[JSON_FORMAT],
+                &[],
+                &CsvOptions::default(),
+            )
+        }
+        Some(("export", m)) => {
+            let source = source_from(m)?;
+            let mut observations = load_observations(source, m.value_of("input").unwrap())?;
+            totals_from(m, &mut observations);
+            let observations = resample_from(m, observations)?;
+            let selection = selection_from(m)?;
+            let formats: Vec<&str> = match m.values_of("formats") {
+                Some(values) => values.collect(),
+                None => vec![JSON_FORMAT, CSV_FORMAT],
+            };
+            export_observations(
+                source,
+                &selection.apply(&observations)?,
+                m.value_of("output").unwrap_or(DEFAULT_OUTPUT_DIR),
+                &formats,
+                &selection.statements(&statements_from(m, source)?),
+                &csv_options_from(m)?,
+            )
+        }
+        Some(("show", m)) => {
+            let source = source_from(m)?;
+            let mut observations = load_observations(source, m.value_of("input").unwrap())?;
+            totals_from(m, &mut observations);
+            show_balance_sheet(
+                &observations,
+                m.value_of("date"),
+                &statements_from(m, source)?,
+            )
+        }
+        Some(("diff", m)) => {
+            let source = source_from(m)?;
+            let mut observations = load_observations(source, m.value_of("input").unwrap())?;
+            totals_from(m, &mut observations);
+            diff_balance_sheets(
+                &observations,
+                m.value_of("from"),
+                m.value_of("to"),
+                m.value_of("period"),
+                &statements_from(m, source)?,
+            )
+        }
+        Some(("explain", m)) => {
+            let source = source_from(m)?;
+            let mut observations = load_observations(source, m.value_of("input").unwrap())?;
+            totals_from(m, &mut observations);
+            explain_change(
+                &observations,
+                m.value_of("concept").unwrap(),
+                m.value_of("from"),
+                m.value_of("to"),
+                m.value_of("period"),
+                m.is_present("children"),
+            )
+        }
+        #[cfg(feature = "sqlite")]
+        Some(("store", m)) => store_observations(
+            source_from(m)?,
+            m.value_of("input").unwrap(),
+            m.value_of("db").unwrap(),
+            m.value_of("vintage"),
+        ),
+        Some(("verify", m)) => verify_output_dir(
+            m.value_of("output").unwrap_or(DEFAULT_OUTPUT_DIR),
+            m.value_of("archive"),
+        ),
+        _ => {
+            // Without subcommand fetch, parse and export everything.
+            let source = source_from(&matches)?;
+            let output_dir = matches.value_of("output").unwrap_or(DEFAULT_OUTPUT_DIR);
+            println!("Value for output dir: {output_dir}");
+            let cache_dir = matches.value_of("cache").unwrap_or(DEFAULT_CACHE_DIR);
+            if !fetch_source(source, output_dir, cache_dir, matches.is_present("force"))? {
+                return Ok(());
+            }
+            let observations =
+                load_observations(source, &format!("{output_dir}/{}", source.data_file()))?;
+            export_observations(
+                source,
+                &observations,
+                output_dir,
+                &[JSON_FORMAT, CSV_FORMAT, SNAPSHOT_FORMAT],
+                source.statements(),
+                &CsvOptions::default(),
+            )
+        }
+    }
 }
@@ -1,5 +1,11 @@
 use actix_files as fs;
 use actix_web::{get, middleware, web, App, HttpRequest, HttpResponse, HttpServer};
+use mulligan::columnar::ObservationStore;
+use mulligan::{snapshot, source, NaiveDate};
+use std::path::Path;
+
+const SNAPSHOT_ENV: &str = "MULLIGAN_SNAPSHOT";
+const DEFAULT_SNAPSHOT: &str = "./tmp/observations.snapshot";
 
 #[get("/resource1/{name}/index.html")]
 async fn index(req: HttpRequest, name: web::Path<String>) -> String {
@@ -17,19 +23,87 @@ async fn no_params() -> &'static str {
     "Hello world!\r\n"
 }
 
+#[get("/sources")]
+async fn sources() -> HttpResponse {
+    let sources: Vec<serde_json::Value> = source::sources()
+        .iter()
+        .map(|s| {
+            serde_json::json!({
+                "id": s.id(),
+                "description": s.description(),
+                "urls": s.urls(),
+                "archive_members": s.archive_members(),
+                "data_file": s.data_file(),
+                "statements": s.statements().iter().map(|c| c.to_string()).collect::<Vec<String>>(),
+            })
+        })
+        .collect();
+    HttpResponse::Ok().json(sources)
+}
+
+#[get("/observations/dates")]
+async fn observation_dates(store: web::Data<Option<ObservationStore>>) -> HttpResponse {
+    let dates = store.as_ref().as_ref().map_or(&[][..], |s| s.dates());
+    HttpResponse::Ok().json(dates)
+}
+
+#[get("/observations/{date}")]
+async fn observation(
+    store: web::Data<Option<ObservationStore>>,
+    date: web::Path<String>,
+) -> HttpResponse {
+    let store = match store.as_ref() {
+        Some(store) => store,
+        None => return HttpResponse::NotFound().finish(),
+    };
+    let date = match date.as_str() {
+        "latest" => store.dates().last().copied(),
+        date => match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+            Ok(date) => Some(date),
+            Err(_) => return HttpResponse::BadRequest().body("invalid date\r\n"),
+        },
+    };
+    match date.and_then(|date| store.balance_sheet(&date)) {
+        Some(bs) => HttpResponse::Ok().json(bs),
+        None => HttpResponse::NotFound().finish(),
+    }
+}
+
+/// Load the observations served by the web server from a snapshot,
+/// written by `fedparser export --formats snapshot`. Balance sheets are
+/// built from the columnar store on every request.
+fn load_snapshot() -> Option<ObservationStore> {
+    let path = std::env::var(SNAPSHOT_ENV).unwrap_or_else(|_| DEFAULT_SNAPSHOT.to_string());
+    match snapshot::load_store(Path::new(&path)) {
+        Ok(store) => {
+            println!("loaded {} balance sheets from {path}", store.len());
+            Some(store)
+        }
+        Err(e) => {
+            println!("no observations loaded from {path}: {e}");
+            None
+        }
+    }
+}
+
 #[actix_rt::main]
 async fn main() -> std::io::Result<()> {
     std::env::set_var("RUST_LOG", "actix_server=info,actix_web=info");
     env_logger::init();
+    let observations = web::Data::new(load_snapshot());
 
-    HttpServer::new(|| {
+    HttpServer::new(move || {
         App::new()
+            .app_data(observations.clone())
             .wrap(middleware::DefaultHeaders::new().add(("X-Version", "0.2")))
             .wrap(middleware::Compress::default())
             .wrap(middleware::Logger::default())
             .service(fs::Files::new("/static", "./web").show_files_listing())
             .service(index)
             .service(no_params)
+            .service(sources)
+            .service(observation_dates)
+            .service(observation)
             .service(
                 web::resource("/resource2/index.html")
                     .wrap(middleware::DefaultHeaders::new().add(("X-Version-R2", "0.3")))
@@ -0,0 +1,223 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::download::{self, FetchOptions, HttpClient};
+
+const NOT_MODIFIED_STATUS: u16 = 304;
+const ETAG_HEADER: &str = "ETag";
+const LAST_MODIFIED_HEADER: &str = "Last-Modified";
+const IF_NONE_MATCH_HEADER: &str = "If-None-Match";
+const IF_MODIFIED_SINCE_HEADER: &str = "If-Modified-Since";
+const ENTRY_EXTENSION: &str = "cache.json";
+
+/// Hex encoded SHA-256 digest of the given bytes.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// Validators and content hash stored for every cached download.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CacheEntry {
+    /// URL the file was downloaded from.
+    pub url: String,
+    /// time the cached content was downloaded.
+    pub downloaded_at: DateTime<Utc>,
+    /// ETag header returned by the server, if any.
+    pub etag: Option<String>,
+    /// Last-Modified header returned by the server, if any.
+    pub last_modified: Option<String>,
+    /// hex encoded SHA-256 of the downloaded content.
+    pub sha256: String,
+}
+
+/// Result of fetching a file through the [`DownloadCache`].
+#[derive(Clone, Debug)]
+pub struct CachedDownload {
+    /// path of the cached file.
+    pub path: PathBuf,
+    /// cache entry describing the cached file.
+    pub entry: CacheEntry,
+    /// true if the content differs from the previously cached one.
+    pub changed: bool,
+}
+
+/// Directory keeping downloaded files along with their HTTP validators,
+/// so they are only downloaded again when the server has a new version.
+pub struct DownloadCache {
+    dir: PathBuf,
+}
+
+impl DownloadCache {
+    /// Create a cache storing its files under the given directory.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> DownloadCache {
+        DownloadCache { dir: dir.into() }
+    }
+
+    fn entry_path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{name}.{ENTRY_EXTENSION}"))
+    }
+
+    /// Cache entry of a previously downloaded file, if it is still in the cache.
+    pub fn entry(&self, name: &str) -> Option<CacheEntry> {
+        if !self.dir.join(name).is_file() {
+            return None;
+        }
+        let text = fs::read_to_string(self.entry_path(name)).ok()?;
+        serde_json::from_str(&text).ok()
+    }
+
+    /// Download `url` into the cache file `name` with a conditional request
+    /// based on the validators of the cached copy.
+    ///
+    /// The file is reported as changed only if the server sent a new
+    /// version and its content hash differs from the cached one.
+    pub fn fetch(
+        &self,
+        client: &dyn HttpClient,
+        url: &str,
+        name: &str,
+        options: &FetchOptions,
+    ) -> Result<CachedDownload, Box<dyn std::error::Error>> {
+        let path = self.dir.join(name);
+        let previous = self.entry(name).filter(|e| e.url == url);
+
+        let mut headers = Vec::new();
+        if let Some(entry) = &previous {
+            if let Some(etag) = &entry.etag {
+                headers.push((IF_NONE_MATCH_HEADER.to_string(), etag.clone()));
+            }
+            if let Some(last_modified) = &entry.last_modified {
+                headers.push((IF_MODIFIED_SINCE_HEADER.to_string(), last_modified.clone()));
+            }
+        }
+
+        let response = download::fetch(client, url, &headers, options)?;
+        if response.status == NOT_MODIFIED_STATUS {
+            let entry = previous
+                .ok_or_else(|| format!("{url} answered not modified without a cached copy"))?;
+            return Ok(CachedDownload {
+                path,
+                entry,
+                changed: false,
+            });
+        }
+
+        let entry = CacheEntry {
+            url: url.to_string(),
+            downloaded_at: Utc::now(),
+            etag: response.header(ETAG_HEADER).map(|v| v.to_string()),
+            last_modified: response.header(LAST_MODIFIED_HEADER).map(|v| v.to_string()),
+            sha256: sha256_hex(&response.body),
+        };
+        let changed = previous.is_none_or(|p| p.sha256 != entry.sha256);
+
+        fs::create_dir_all(&self.dir)?;
+        download::write_file(&path, &response.body)?;
+        fs::write(self.entry_path(name), serde_json::to_string_pretty(&entry)?)?;
+
+        Ok(CachedDownload {
+            path,
+            entry,
+            changed,
+        })
+    }
+
+    /// Directory holding the cached files.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::download::{HttpRequest, HttpResponse};
+    use std::cell::RefCell;
+    use std::time::Duration;
+
+    struct VersionedClient {
+        body: Vec<u8>,
+        requests: RefCell<Vec<HttpRequest>>,
+    }
+
+    impl HttpClient for VersionedClient {
+        fn get(&self, request: &HttpRequest) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+            self.requests.borrow_mut().push(request.clone());
+            let etag = format!("\"{}\"", sha256_hex(&self.body));
+            let matches = request
+                .headers
+                .iter()
+                .any(|(n, v)| n == IF_NONE_MATCH_HEADER && *v == etag);
+            Ok(HttpResponse {
+                status: if matches { NOT_MODIFIED_STATUS } else { 200 },
+                headers: vec![(ETAG_HEADER.to_string(), etag)],
+                body: if matches {
+                    Vec::new()
+                } else {
+                    self.body.clone()
+                },
+            })
+        }
+    }
+
+    struct NotModifiedClient(String);
+
+    impl HttpClient for NotModifiedClient {
+        fn get(&self, _: &HttpRequest) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+            Ok(HttpResponse {
+                status: NOT_MODIFIED_STATUS,
+                headers: vec![(ETAG_HEADER.to_string(), self.0.clone())],
+                body: Vec::new(),
+            })
+        }
+    }
+
+    #[test]
+    fn conditional_fetch_test() {
+        let dir = std::env::temp_dir().join(format!("mulligan_cache_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let cache = DownloadCache::new(&dir);
+        let options = FetchOptions {
+            backoff: Duration::ZERO,
+            ..FetchOptions::default()
+        };
+        let mut client = VersionedClient {
+            body: b"v1".to_vec(),
+            requests: RefCell::new(Vec::new()),
+        };
+
+        let first = cache
+            .fetch(&client, "http://h/", "h41.zip", &options)
+            .unwrap();
+        assert!(first.changed);
+        assert!(client.requests.borrow()[0].headers.is_empty());
+
+        let second = cache
+            .fetch(&client, "http://h/", "h41.zip", &options)
+            .unwrap();
+        assert!(!second.changed);
+        assert_eq!(second.entry, first.entry);
+        assert_eq!(fs::read(&second.path).unwrap(), b"v1");
+
+        client.body = b"v2".to_vec();
+        let third = cache
+            .fetch(&client, "http://h/", "h41.zip", &options)
+            .unwrap();
+        assert!(third.changed);
+        assert_eq!(fs::read(&third.path).unwrap(), b"v2");
+
+        fs::remove_file(&third.path).unwrap();
+        let stale = cache.entry_path("h41.zip");
+        let etag = format!("\"{}\"", sha256_hex(b"v2"));
+        let client = NotModifiedClient(etag);
+        assert!(cache
+            .fetch(&client, "http://h/", "h41.zip", &options)
+            .is_err());
+        assert!(!dir.join("h41.zip").exists());
+        assert!(stale.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,345 @@
+use chrono::{Duration, NaiveDate};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::types::{BalanceSheet, Concept, ConceptType, ObservationMap, PATH_SEPARATOR};
+
+/// Change of the value of a concept between two balance sheets, along with
+/// the changes of its children.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ConceptChange {
+    /// accounting concept path.
+    pub path: String,
+    /// value in the first balance sheet, 0 if the concept is not there.
+    pub from: i64,
+    /// value in the second balance sheet, 0 if the concept is not there.
+    pub to: i64,
+    /// absolute change of the value.
+    pub change: i64,
+    /// change in percent of the first value, `None` if it is 0.
+    pub percent: Option<f64>,
+    /// changes of the children, in the order of the first balance sheet
+    /// followed by the children only found in the second one.
+    pub children: Vec<ConceptChange>,
+}
+
+impl ConceptChange {
+    fn new(path: &str, from: Option<&Concept>, to: Option<&Concept>) -> ConceptChange {
+        let value = |c: Option<&Concept>| c.map_or(0, |c| c.value);
+        let (from_value, to_value) = (value(from), value(to));
+
+        let mut paths: Vec<&str> = Vec::new();
+        for c in from.into_iter().chain(to) {
+            for child in c.children() {
+                if !paths.contains(&child.path.as_str()) {
+                    paths.push(&child.path);
+                }
+            }
+        }
+        fn child<'a>(c: Option<&'a Concept>, path: &str) -> Option<&'a Concept> {
+            c?.children().iter().find(|child| child.path == path)
+        }
+
+        ConceptChange {
+            path: path.to_string(),
+            from: from_value,
+            to: to_value,
+            change: to_value - from_value,
+            percent: (from_value != 0)
+                .then(|| (to_value - from_value) as f64 * 100.0 / from_value.abs() as f64),
+            children: paths
+                .into_iter()
+                .map(|path| ConceptChange::new(path, child(from, path), child(to, path)))
+                .collect(),
+        }
+    }
+
+    /// concept name.
+    pub fn name(&self) -> &str {
+        self.path
+            .rsplit_once(PATH_SEPARATOR)
+            .map_or(&self.path, |(_, name)| name)
+    }
+
+    /// Depth of the concept in its statement tree, 0 for the statement itself.
+    pub fn depth(&self) -> usize {
+        self.path.matches(PATH_SEPARATOR).count()
+    }
+
+    /// Iterate in pre-order over this change and the changes below it.
+    pub fn iter(&self) -> impl Iterator<Item = &ConceptChange> {
+        let mut stack = vec![self];
+        std::iter::from_fn(move || {
+            let change = stack.pop()?;
+            stack.extend(change.children.iter().rev());
+            Some(change)
+        })
+    }
+
+    /// Get the change of the concept in `path`.
+    pub fn get(&self, path: &str) -> Option<&ConceptChange> {
+        self.iter().find(|c| c.path == path)
+    }
+
+    /// Return true if the concept has no children.
+    pub fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    /// Contributions of the children of this concept to its change, ranked
+    /// by absolute change, largest first.
+    ///
+    /// Intermediate concepts without series only have a value once rolled
+    /// up, see [`BalanceSheet::rollup`], so roll up both balance sheets
+    /// before diffing them.
+    pub fn contributions(&self) -> Vec<Contribution<'_>> {
+        self.rank(self.children.iter())
+    }
+
+    /// Contributions of the leaf concepts below this one to its change,
+    /// ranked as in [`ConceptChange::contributions`].
+    pub fn leaf_contributions(&self) -> Vec<Contribution<'_>> {
+        self.rank(self.iter().skip(1).filter(|c| c.is_leaf()))
+    }
+
+    fn rank<'a>(&self, concepts: impl Iterator<Item = &'a ConceptChange>) -> Vec<Contribution<'a>> {
+        let mut contributions: Vec<Contribution<'_>> = concepts
+            .map(|concept| Contribution {
+                concept,
+                share: (self.change != 0)
+                    .then(|| concept.change as f64 * 100.0 / self.change as f64),
+            })
+            .collect();
+        contributions.sort_by_key(|c| std::cmp::Reverse(c.concept.change.abs()));
+        contributions
+    }
+}
+
+/// Contribution of a concept to the change of one of its ancestors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Contribution<'a> {
+    /// change of the concept.
+    pub concept: &'a ConceptChange,
+    /// change of the concept in percent of the change of the ancestor,
+    /// `None` if the ancestor did not change.
+    pub share: Option<f64>,
+}
+
+impl Concept {
+    /// Changes of this concept and its children up to `other`, matching
+    /// concepts by path.
+    pub fn diff(&self, other: &Concept) -> ConceptChange {
+        ConceptChange::new(&self.path, Some(self), Some(other))
+    }
+}
+
+/// Changes between the assets, liabilities and capital of two balance sheets.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BalanceSheetChange {
+    assets: ConceptChange,
+    liabilities: ConceptChange,
+    capital: ConceptChange,
+}
+
+impl BalanceSheetChange {
+    /// Get the changes of a statement.
+    pub fn get_concept(&self, ctype: &ConceptType) -> &ConceptChange {
+        match ctype {
+            ConceptType::Assets => &self.assets,
+            ConceptType::Liabilities => &self.liabilities,
+            ConceptType::Capital => &self.capital,
+        }
+    }
+
+    /// Get the change of the concept in `path`.
+    pub fn get(&self, path: &str) -> Option<&ConceptChange> {
+        self.get_concept(&ConceptType::from_path(path)?).get(path)
+    }
+
+    /// Contributions to the change of the concept in `path`, see
+    /// [`ConceptChange::contributions`].
+    pub fn contributions(&self, path: &str) -> Option<Vec<Contribution<'_>>> {
+        Some(self.get(path)?.contributions())
+    }
+
+    /// Contributions of the leaves below the concept in `path` to its
+    /// change, see [`ConceptChange::leaf_contributions`].
+    pub fn leaf_contributions(&self, path: &str) -> Option<Vec<Contribution<'_>>> {
+        Some(self.get(path)?.leaf_contributions())
+    }
+}
+
+impl BalanceSheet {
+    /// Changes of every concept from this balance sheet to `other`.
+    pub fn diff(&self, other: &BalanceSheet) -> BalanceSheetChange {
+        let diff = |ctype: ConceptType| self.get_concept(&ctype).diff(other.get_concept(&ctype));
+        BalanceSheetChange {
+            assets: diff(ConceptType::Assets),
+            liabilities: diff(ConceptType::Liabilities),
+            capital: diff(ConceptType::Capital),
+        }
+    }
+}
+
+/// Period changes are computed over, as in the H.4.1 change columns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangePeriod {
+    /// week over week.
+    Week,
+    /// over four weeks.
+    FourWeeks,
+    /// over thirteen weeks.
+    ThirteenWeeks,
+    /// year over year, i.e. 52 weeks.
+    Year,
+}
+
+impl ChangePeriod {
+    /// Number of weeks of the period.
+    pub fn weeks(&self) -> i64 {
+        match self {
+            ChangePeriod::Week => 1,
+            ChangePeriod::FourWeeks => 4,
+            ChangePeriod::ThirteenWeeks => 13,
+            ChangePeriod::Year => 52,
+        }
+    }
+}
+
+impl fmt::Display for ChangePeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let period = match self {
+            ChangePeriod::Week => "1w",
+            ChangePeriod::FourWeeks => "4w",
+            ChangePeriod::ThirteenWeeks => "13w",
+            ChangePeriod::Year => "1y",
+        };
+        write!(f, "{period}")
+    }
+}
+
+impl FromStr for ChangePeriod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ChangePeriod, Self::Err> {
+        match s {
+            "1w" | "wow" => Ok(ChangePeriod::Week),
+            "4w" => Ok(ChangePeriod::FourWeeks),
+            "13w" => Ok(ChangePeriod::ThirteenWeeks),
+            "1y" | "52w" | "yoy" => Ok(ChangePeriod::Year),
+            _ => Err(format!("unknown change period: {s}")),
+        }
+    }
+}
+
+/// Changes over a period between the balance sheets of observations.
+pub trait ObservationChanges {
+    /// Date a change over `period` up to `date` is computed from: the date
+    /// `period` before, or the latest date in the week before it if that
+    /// one was not published.
+    fn base_date(&self, date: &NaiveDate, period: ChangePeriod) -> Option<NaiveDate>;
+
+    /// Changes of the balance sheet of `date` over `period`.
+    fn change(&self, date: &NaiveDate, period: ChangePeriod) -> Option<BalanceSheetChange>;
+
+    /// Changes over `period` of every date with a balance sheet to compare with.
+    fn changes(&self, period: ChangePeriod) -> BTreeMap<NaiveDate, BalanceSheetChange>;
+}
+
+impl ObservationChanges for ObservationMap {
+    fn base_date(&self, date: &NaiveDate, period: ChangePeriod) -> Option<NaiveDate> {
+        let base = *date - Duration::weeks(period.weeks());
+        self.range(base - Duration::days(6)..=base)
+            .next_back()
+            .map(|(date, _)| *date)
+    }
+
+    fn change(&self, date: &NaiveDate, period: ChangePeriod) -> Option<BalanceSheetChange> {
+        let base = self.base_date(date, period)?;
+        Some(self.get(&base)?.diff(self.get(date)?))
+    }
+
+    fn changes(&self, period: ChangePeriod) -> BTreeMap<NaiveDate, BalanceSheetChange> {
+        self.keys()
+            .filter_map(|date| Some((*date, self.change(date, period)?)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fed::parse_h41_data;
+    use std::fs;
+
+    #[test]
+    fn change_test() {
+        let text = fs::read_to_string("tests/data/FRB_H41_2020.xml").unwrap();
+        let obs = parse_h41_data(&text).unwrap();
+        let date = |d: &str| NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap();
+        let (from, to) = (date("2020-05-20"), date("2020-05-27"));
+
+        let change = obs[&from].diff(&obs[&to]);
+        let assets = change.get_concept(&ConceptType::Assets);
+        assert_eq!(assets.from, obs[&from].get("Assets").unwrap().value);
+        assert_eq!(assets.to, 7097316);
+        assert_eq!(assets.change, assets.to - assets.from);
+        let percent = assets.change as f64 * 100.0 / assets.from as f64;
+        assert!((assets.percent.unwrap() - percent).abs() < 1e-9);
+        assert_eq!(
+            assets.iter().count(),
+            obs[&to].get_concept(&ConceptType::Assets).iter().count()
+        );
+        assert_eq!(change.get("Capital/Surplus").unwrap().name(), "Surplus");
+
+        let contributions = change.contributions("Assets").unwrap();
+        assert_eq!(contributions.len(), assets.children.len());
+        assert!(contributions
+            .windows(2)
+            .all(|w| w[0].concept.change.abs() >= w[1].concept.change.abs()));
+
+        let (mut from_bs, mut to_bs) = (obs[&from].clone(), obs[&to].clone());
+        from_bs.rollup();
+        to_bs.rollup();
+        let rolled = from_bs.diff(&to_bs);
+        let facilities = rolled
+            .get("Assets/Liquidity and Credit Facilities")
+            .unwrap();
+        assert!(facilities.change != 0);
+        let children: f64 = facilities
+            .contributions()
+            .iter()
+            .map(|c| c.share.unwrap())
+            .sum();
+        assert!((children - 100.0).abs() < 1e-6);
+        let leaves = facilities.leaf_contributions();
+        assert!(leaves.iter().all(|c| c.concept.is_leaf()));
+        assert_eq!(
+            leaves.len(),
+            facilities.iter().filter(|c| c.is_leaf()).count()
+        );
+        let shares: f64 = leaves.iter().map(|c| c.share.unwrap()).sum();
+        let covered = leaves.iter().map(|c| c.concept.change).sum::<i64>() as f64;
+        assert!((shares - covered * 100.0 / facilities.change as f64).abs() < 1e-6);
+        assert!(change.contributions("Assets/Unknown").is_none());
+
+        let mut bs = obs[&to].clone();
+        bs.get_concept_mut(&ConceptType::Capital)
+            .insert_concept("Capital/New", "NEW");
+        let new = obs[&from].diff(&bs);
+        let new = new.get("Capital/New").unwrap();
+        assert_eq!((new.from, new.percent), (0, None));
+
+        assert_eq!(obs.base_date(&to, ChangePeriod::Week), Some(from));
+        assert_eq!(obs.change(&to, ChangePeriod::Week), Some(change));
+        assert_eq!(obs.changes(ChangePeriod::Week).len(), obs.len() - 1);
+        assert_eq!(obs.changes(ChangePeriod::FourWeeks).len(), 1);
+        assert!(obs.change(&to, ChangePeriod::Year).is_none());
+        assert_eq!(
+            "13w".parse::<ChangePeriod>(),
+            Ok(ChangePeriod::ThirteenWeeks)
+        );
+    }
+}
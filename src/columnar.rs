@@ -0,0 +1,537 @@
+use chrono::NaiveDate;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::series::TimeSeries;
+use crate::types::{
+    BalanceSheet, Concept, ConceptType, ObservationMap, ObservationStatus, PATH_SEPARATOR,
+    UNDEFINED_SERIES_NAME,
+};
+
+/// Concept of a [`ConceptSchema`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SchemaConcept {
+    /// full concept path.
+    pub path: String,
+    /// series the concept was extracted from.
+    pub series_name: String,
+    /// currency of the concept values.
+    pub currency: String,
+    /// index of the parent concept in the schema, `None` for statement roots.
+    pub parent: Option<usize>,
+}
+
+/// Concept tree of a balance sheet stored once, as the list of its
+/// concepts. The index of a concept in the list is its column in an
+/// [`ObservationStore`].
+#[derive(Clone, Debug)]
+pub struct ConceptSchema {
+    concepts: Vec<SchemaConcept>,
+    // columns sorted by concept path.
+    by_path: Vec<usize>,
+    // columns of the children of every concept, in schema order.
+    children: Vec<Vec<usize>>,
+    // columns of the statements, in balance sheet order.
+    roots: [usize; 3],
+}
+
+fn balance_sheet_concepts(bs: &BalanceSheet) -> impl Iterator<Item = &Concept> {
+    ConceptType::ALL
+        .iter()
+        .flat_map(move |ctype| bs.get_concept(ctype).iter())
+}
+
+fn add_concepts(concept: &Concept, parent: Option<usize>, concepts: &mut Vec<SchemaConcept>) {
+    let i = concepts.len();
+    concepts.push(SchemaConcept {
+        path: concept.path.clone(),
+        series_name: concept.series_name.clone(),
+        currency: concept.currency.clone(),
+        parent,
+    });
+    for child in concept.children.iter() {
+        add_concepts(child, Some(i), concepts);
+    }
+}
+
+impl ConceptSchema {
+    /// Build a schema from its concepts, every parent must precede its
+    /// children and be named by the path of the child.
+    pub fn new(concepts: Vec<SchemaConcept>) -> Result<ConceptSchema, Box<dyn std::error::Error>> {
+        let mut roots = [None; 3];
+        for (i, c) in concepts.iter().enumerate() {
+            let parent_path = c.path.rsplit_once(PATH_SEPARATOR).map(|(parent, _)| parent);
+            match (c.parent, parent_path) {
+                (Some(parent), _) if parent >= i => {
+                    return Err(format!("concept {} listed before its parent", c.path).into());
+                }
+                (Some(parent), Some(path)) if concepts[parent].path == path => {}
+                (None, None) => {
+                    let ctype = ConceptType::ALL
+                        .iter()
+                        .position(|ctype| c.path == ctype.to_string())
+                        .ok_or_else(|| format!("concept {} outside any statement", c.path))?;
+                    if roots[ctype].replace(i).is_some() {
+                        return Err(format!("duplicated concept {}", c.path).into());
+                    }
+                }
+                _ => return Err(format!("concept {} does not match its parent", c.path).into()),
+            }
+        }
+        let statement = |i: usize| {
+            roots[i].ok_or_else(|| format!("schema without {} statement", ConceptType::ALL[i]))
+        };
+        let roots = [statement(0)?, statement(1)?, statement(2)?];
+
+        let schema = ConceptSchema::with_roots(concepts, roots);
+        if let Some(pair) = schema
+            .by_path
+            .windows(2)
+            .find(|pair| schema.concepts[pair[0]].path == schema.concepts[pair[1]].path)
+        {
+            return Err(format!("duplicated concept {}", schema.concepts[pair[0]].path).into());
+        }
+        Ok(schema)
+    }
+
+    fn with_roots(concepts: Vec<SchemaConcept>, roots: [usize; 3]) -> ConceptSchema {
+        let mut by_path: Vec<usize> = (0..concepts.len()).collect();
+        by_path.sort_unstable_by(|a, b| concepts[*a].path.cmp(&concepts[*b].path));
+        let mut children = vec![Vec::new(); concepts.len()];
+        for (i, c) in concepts.iter().enumerate() {
+            if let Some(parent) = c.parent {
+                children[parent].push(i);
+            }
+        }
+
+        ConceptSchema {
+            concepts,
+            by_path,
+            children,
+            roots,
+        }
+    }
+
+    /// Build the schema of the concept tree of a balance sheet.
+    pub fn from_balance_sheet(bs: &BalanceSheet) -> ConceptSchema {
+        let mut concepts = Vec::new();
+        let mut roots = [0; 3];
+        for (root, ctype) in roots.iter_mut().zip(ConceptType::ALL.iter()) {
+            *root = concepts.len();
+            add_concepts(bs.get_concept(ctype), None, &mut concepts);
+        }
+        ConceptSchema::with_roots(concepts, roots)
+    }
+
+    /// Concepts of the schema in column order.
+    pub fn concepts(&self) -> &[SchemaConcept] {
+        &self.concepts
+    }
+
+    /// Number of concepts of the schema.
+    pub fn len(&self) -> usize {
+        self.concepts.len()
+    }
+
+    /// Return true if the schema has no concepts.
+    pub fn is_empty(&self) -> bool {
+        self.concepts.is_empty()
+    }
+
+    /// Index of a concept in the schema given its path.
+    pub fn position(&self, path: &str) -> Option<usize> {
+        self.by_path
+            .binary_search_by(|i| self.concepts[*i].path.as_str().cmp(path))
+            .ok()
+            .map(|i| self.by_path[i])
+    }
+
+    /// Balance sheet with the concept tree of the schema and no values.
+    pub fn template(&self) -> BalanceSheet {
+        self.balance_sheet(|_| (0, ObservationStatus::default()))
+    }
+
+    // Columns of the concepts in the order the statements of the balance
+    // sheets built from the schema are iterated, see [`Concept::iter`].
+    fn tree_order(&self) -> Vec<usize> {
+        let mut order = Vec::with_capacity(self.len());
+        let mut stack: Vec<usize> = self.roots.iter().rev().copied().collect();
+        while let Some(i) = stack.pop() {
+            order.push(i);
+            stack.extend(self.children[i].iter().rev());
+        }
+        order
+    }
+
+    // Balance sheet with the cell of every column given by `cell`.
+    fn balance_sheet(&self, cell: impl Fn(usize) -> (i64, ObservationStatus)) -> BalanceSheet {
+        let [assets, liabilities, capital] = self.roots.map(|root| self.concept(root, &cell));
+        BalanceSheet::new(assets, liabilities, capital)
+    }
+
+    fn concept(&self, i: usize, cell: &impl Fn(usize) -> (i64, ObservationStatus)) -> Concept {
+        let c = &self.concepts[i];
+        let (value, status) = cell(i);
+        Concept {
+            value,
+            status,
+            currency: c.currency.clone(),
+            children: self.children[i]
+                .iter()
+                .map(|child| self.concept(*child, cell))
+                .collect(),
+            ..Concept::new(&c.path, &c.series_name)
+        }
+    }
+}
+
+// Set the values of a concept and the concepts below it, in tree order.
+// Only values change, so the index shared with the template is kept.
+fn fill(concept: &mut Concept, cells: &mut impl Iterator<Item = (i64, ObservationStatus)>) {
+    if let Some((value, status)) = cells.next() {
+        concept.value = value;
+        concept.status = status;
+    }
+    for child in concept.children.iter_mut() {
+        fill(child, cells);
+    }
+}
+
+/// Observations held in columns: the concept schema is shared and every
+/// concept keeps a column of values indexed by date.
+///
+/// Balance sheets are only materialized on demand, see
+/// [`ObservationStore::balance_sheet`].
+#[derive(Clone, Debug)]
+pub struct ObservationStore {
+    schema: Arc<ConceptSchema>,
+    dates: Vec<NaiveDate>,
+    values: Vec<Vec<i64>>,
+    statuses: Vec<Vec<ObservationStatus>>,
+}
+
+impl ObservationStore {
+    /// Create an empty store for the given schema.
+    pub fn new(schema: Arc<ConceptSchema>) -> ObservationStore {
+        ObservationStore::with_dates(schema, Vec::new())
+    }
+
+    /// Create a store for the given schema and dates, without values.
+    /// Cells are then written in place with [`ObservationStore::set_at`].
+    pub fn with_dates(schema: Arc<ConceptSchema>, mut dates: Vec<NaiveDate>) -> ObservationStore {
+        dates.sort_unstable();
+        dates.dedup();
+        let n = schema.len();
+        ObservationStore {
+            schema,
+            values: vec![vec![0; dates.len()]; n],
+            statuses: vec![vec![ObservationStatus::default(); dates.len()]; n],
+            dates,
+        }
+    }
+
+    /// Build a store from balance sheets sharing the concept tree of the first one.
+    pub fn from_observations(
+        obs: &ObservationMap,
+    ) -> Result<ObservationStore, Box<dyn std::error::Error>> {
+        let schema = match obs.values().next() {
+            Some(bs) => ConceptSchema::from_balance_sheet(bs),
+            None => {
+                let root =
+                    |ctype: ConceptType| Concept::new(&ctype.to_string(), UNDEFINED_SERIES_NAME);
+                ConceptSchema::from_balance_sheet(&BalanceSheet::new(
+                    root(ConceptType::Assets),
+                    root(ConceptType::Liabilities),
+                    root(ConceptType::Capital),
+                ))
+            }
+        };
+        let mut store =
+            ObservationStore::with_dates(Arc::new(schema), obs.keys().copied().collect());
+        for (i, (date, bs)) in obs.iter().enumerate() {
+            let mut count = 0;
+            for (c, concept) in balance_sheet_concepts(bs).enumerate() {
+                if store.schema.concepts.get(c).map(|s| s.path.as_str()) != Some(&concept.path) {
+                    return Err(format!("balance sheet of {date} does not match the schema").into());
+                }
+                store.values[c][i] = concept.value;
+                store.statuses[c][i] = concept.status;
+                count += 1;
+            }
+            if count != store.schema.len() {
+                return Err(format!("balance sheet of {date} does not match the schema").into());
+            }
+        }
+        Ok(store)
+    }
+
+    /// Schema shared by every date.
+    pub fn schema(&self) -> &Arc<ConceptSchema> {
+        &self.schema
+    }
+
+    /// Dates of the store in ascending order.
+    pub fn dates(&self) -> &[NaiveDate] {
+        &self.dates
+    }
+
+    /// Number of dates of the store.
+    pub fn len(&self) -> usize {
+        self.dates.len()
+    }
+
+    /// Return true if the store has no dates.
+    pub fn is_empty(&self) -> bool {
+        self.dates.is_empty()
+    }
+
+    /// Index of a date in the store.
+    pub fn date_index(&self, date: &NaiveDate) -> Option<usize> {
+        self.dates.binary_search(date).ok()
+    }
+
+    /// Add a date without values to the store if it is not already there
+    /// and return its index.
+    ///
+    /// Dates inserted before the last one shift every column, build the
+    /// store with [`ObservationStore::with_dates`] when the dates are known.
+    pub fn insert_date(&mut self, date: NaiveDate) -> usize {
+        match self.dates.binary_search(&date) {
+            Ok(i) => i,
+            Err(i) => {
+                self.dates.insert(i, date);
+                for column in self.values.iter_mut() {
+                    column.insert(i, 0);
+                }
+                for column in self.statuses.iter_mut() {
+                    column.insert(i, ObservationStatus::default());
+                }
+                i
+            }
+        }
+    }
+
+    /// Set the value of the concept with index `concept` at a date,
+    /// adding the date if needed.
+    pub fn set(&mut self, date: NaiveDate, concept: usize, value: i64, status: ObservationStatus) {
+        let i = self.insert_date(date);
+        self.set_at(i, concept, value, status);
+    }
+
+    /// Set the value of the concept with index `concept` at the date with
+    /// index `date`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either index is out of bounds.
+    pub fn set_at(&mut self, date: usize, concept: usize, value: i64, status: ObservationStatus) {
+        self.values[concept][date] = value;
+        self.statuses[concept][date] = status;
+    }
+
+    /// Values of a concept for every date.
+    pub fn column(&self, path: &str) -> Option<&[i64]> {
+        Some(&self.values[self.schema.position(path)?])
+    }
+
+    /// Status of the values of a concept for every date.
+    pub fn status_column(&self, path: &str) -> Option<&[ObservationStatus]> {
+        Some(&self.statuses[self.schema.position(path)?])
+    }
+
+    /// Time series of a concept, read from its columns.
+    pub fn time_series(&self, path: &str) -> Option<TimeSeries> {
+        let i = self.schema.position(path)?;
+        let concept = &self.schema.concepts()[i];
+        Some(TimeSeries {
+            path: concept.path.clone(),
+            series_name: concept.series_name.clone(),
+            dates: self.dates.clone(),
+            values: self.values[i]
+                .iter()
+                .zip(self.statuses[i].iter())
+                .map(|(value, status)| status.has_value().then_some(*value))
+                .collect(),
+            statuses: self.statuses[i].clone(),
+        })
+    }
+
+    /// Value of a concept at a date.
+    pub fn value(&self, date: &NaiveDate, path: &str) -> Option<i64> {
+        let i = self.date_index(date)?;
+        Some(self.column(path)?[i])
+    }
+
+    fn balance_sheet_at(&self, i: usize) -> BalanceSheet {
+        self.schema
+            .balance_sheet(|c| (self.values[c][i], self.statuses[c][i]))
+    }
+
+    /// Materialize the balance sheet of a date.
+    pub fn balance_sheet(&self, date: &NaiveDate) -> Option<BalanceSheet> {
+        let i = self.date_index(date)?;
+        Some(self.balance_sheet_at(i))
+    }
+
+    /// Materialize the balance sheets of every date.
+    ///
+    /// Balance sheets are cloned from a single template, so they share
+    /// its concept index.
+    pub fn to_observations(&self) -> ObservationMap {
+        let template = self.schema.template();
+        let order = self.schema.tree_order();
+        let mut obs: ObservationMap = BTreeMap::new();
+        for (i, date) in self.dates.iter().enumerate() {
+            let mut bs = template.clone();
+            let mut cells = order
+                .iter()
+                .map(|c| (self.values[*c][i], self.statuses[*c][i]));
+            for ctype in ConceptType::ALL.iter() {
+                fill(bs.get_concept_mut(ctype), &mut cells);
+            }
+            obs.insert(*date, bs);
+        }
+        obs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fed::{
+        parse_h41_data, parse_h41_data_with_options, parse_h41_store, parse_h41_store_with_options,
+        ParseOptions,
+    };
+    use crate::series::ObservationSeries;
+    use std::fs;
+
+    #[test]
+    fn observation_store_test() {
+        let text = fs::read_to_string("tests/data/FRB_H41_2010.xml").unwrap();
+        let obs = parse_h41_data(&text).unwrap();
+        let store = parse_h41_store(&text).unwrap();
+        assert_eq!(store.len(), obs.len());
+        assert_eq!(
+            serde_json::to_value(store.to_observations()).unwrap(),
+            serde_json::to_value(&obs).unwrap()
+        );
+        assert_eq!(
+            serde_json::to_value(
+                ObservationStore::from_observations(&obs)
+                    .unwrap()
+                    .to_observations()
+            )
+            .unwrap(),
+            serde_json::to_value(&obs).unwrap()
+        );
+
+        let options = ParseOptions {
+            rollup: true,
+            residuals: true,
+        };
+        assert_eq!(
+            serde_json::to_value(
+                parse_h41_store_with_options(&text, &options)
+                    .unwrap()
+                    .to_observations()
+            )
+            .unwrap(),
+            serde_json::to_value(parse_h41_data_with_options(&text, &options).unwrap()).unwrap()
+        );
+        let materialized = store.to_observations();
+        let (first, second) = (
+            materialized.values().next().unwrap(),
+            materialized.values().nth(1).unwrap(),
+        );
+        assert!(Arc::ptr_eq(
+            first.get_concept(&ConceptType::Assets).index.get().unwrap(),
+            second
+                .get_concept(&ConceptType::Assets)
+                .index
+                .get()
+                .unwrap()
+        ));
+
+        let date = store.dates()[0];
+        assert_eq!(store.value(&date, "Capital/Capital paid in"), Some(25651));
+        assert_eq!(store.column("Capital").unwrap().len(), obs.len());
+        assert!(store.column("Capital/Unknown").is_none());
+        assert_eq!(store.time_series("Assets"), obs.time_series("Assets"));
+
+        let dates: Vec<NaiveDate> = obs.keys().rev().copied().chain(Some(date)).collect();
+        let mut sized = ObservationStore::with_dates(store.schema().clone(), dates);
+        assert_eq!(sized.dates(), store.dates());
+        let last = sized.len() - 1;
+        let paid_in = store.schema().position("Capital/Capital paid in").unwrap();
+        sized.set_at(last, paid_in, 7, ObservationStatus::Available);
+        assert_eq!(
+            sized.value(&store.dates()[last], "Capital/Capital paid in"),
+            Some(7)
+        );
+        assert_eq!(sized.date_index(&date), Some(0));
+        let schema = store.schema();
+        let position = schema.position("Capital/Capital paid in").unwrap();
+        assert_eq!(
+            schema.concepts()[position].parent,
+            schema.position("Capital")
+        );
+        assert!(ConceptSchema::new(schema.concepts()[1..].to_vec()).is_err());
+    }
+
+    fn schema_concept(path: &str, parent: Option<usize>) -> SchemaConcept {
+        SchemaConcept {
+            path: path.to_string(),
+            series_name: UNDEFINED_SERIES_NAME.to_string(),
+            currency: "USD".to_string(),
+            parent,
+        }
+    }
+
+    #[test]
+    fn schema_order_test() {
+        let date = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        let schema = ConceptSchema::new(vec![
+            schema_concept("Capital", None),
+            schema_concept("Assets", None),
+            schema_concept("Assets/A", Some(1)),
+            schema_concept("Assets/B", Some(1)),
+            schema_concept("Assets/A/x", Some(2)),
+            schema_concept("Liabilities", None),
+        ])
+        .unwrap();
+        let mut store = ObservationStore::new(Arc::new(schema));
+        let b = store.schema().position("Assets/B").unwrap();
+        store.set(date, b, 42, ObservationStatus::Available);
+        let capital = store.schema().position("Capital").unwrap();
+        store.set(date, capital, 7, ObservationStatus::Available);
+        assert_eq!(store.value(&date, "Assets/B"), Some(42));
+
+        let bs = store.balance_sheet(&date).unwrap();
+        let assets = bs.get_concept(&ConceptType::Assets);
+        assert_eq!(assets.value, 0);
+        assert_eq!(assets.get("Assets/B").unwrap().value, 42);
+        assert_eq!(assets.get("Assets/A/x").unwrap().value, 0);
+        assert_eq!(
+            assets.iter().map(|c| c.path.as_str()).collect::<Vec<_>>(),
+            ["Assets", "Assets/A", "Assets/A/x", "Assets/B"]
+        );
+        assert_eq!(bs.get_concept(&ConceptType::Capital).value, 7);
+        assert_eq!(bs.get_concept(&ConceptType::Liabilities).value, 0);
+
+        assert!(ConceptSchema::new(vec![
+            schema_concept("Assets", None),
+            schema_concept("Liabilities", None),
+            schema_concept("Capital", None),
+            schema_concept("Assets/A", Some(1)),
+        ])
+        .is_err());
+        assert!(ConceptSchema::new(vec![
+            schema_concept("Assets", None),
+            schema_concept("Liabilities", None),
+            schema_concept("Capital", None),
+            schema_concept("Assets/A", Some(0)),
+            schema_concept("Assets/A", Some(0)),
+        ])
+        .is_err());
+    }
+}
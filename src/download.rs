@@ -0,0 +1,226 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 60;
+const DEFAULT_RETRIES: u32 = 3;
+const DEFAULT_BACKOFF_MILLIS: u64 = 500;
+const TOO_MANY_REQUESTS_STATUS: u16 = 429;
+
+/// HTTP GET request sent through an [`HttpClient`].
+#[derive(Clone, Debug)]
+pub struct HttpRequest {
+    /// requested URL.
+    pub url: String,
+    /// extra request headers as (name, value) pairs.
+    pub headers: Vec<(String, String)>,
+    /// maximum time to wait for the whole response.
+    pub timeout: Duration,
+}
+
+/// HTTP response returned by an [`HttpClient`].
+#[derive(Clone, Debug)]
+pub struct HttpResponse {
+    /// HTTP status code.
+    pub status: u16,
+    /// response headers as (name, value) pairs.
+    pub headers: Vec<(String, String)>,
+    /// response body.
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    /// Value of the first header with the given name, compared case insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// HTTP transport used to download releases.
+///
+/// Implementations return `Ok` for any response received from the server,
+/// whatever its status, and `Err` only for transport failures.
+pub trait HttpClient {
+    /// Send a GET request.
+    fn get(&self, request: &HttpRequest) -> Result<HttpResponse, Box<dyn std::error::Error>>;
+}
+
+/// [`HttpClient`] backed by a blocking reqwest client.
+///
+/// As any blocking reqwest client it must not be used from inside an async runtime.
+#[derive(Default)]
+pub struct ReqwestClient {
+    client: reqwest::blocking::Client,
+}
+
+impl HttpClient for ReqwestClient {
+    fn get(&self, request: &HttpRequest) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+        let mut builder = self.client.get(&request.url).timeout(request.timeout);
+        for (name, value) in &request.headers {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        let response = builder.send()?;
+        let status = response.status().as_u16();
+        let headers = response
+            .headers()
+            .iter()
+            .filter_map(|(n, v)| Some((n.to_string(), v.to_str().ok()?.to_string())))
+            .collect();
+        let body = response.bytes()?.to_vec();
+
+        Ok(HttpResponse {
+            status,
+            headers,
+            body,
+        })
+    }
+}
+
+/// Options controlling how releases are downloaded.
+#[derive(Clone, Debug)]
+pub struct FetchOptions {
+    /// base URL (scheme, host and port) replacing the release default one.
+    pub base_url: Option<String>,
+    /// timeout for every request attempt.
+    pub timeout: Duration,
+    /// number of retries after a failed attempt.
+    pub retries: u32,
+    /// wait before the first retry, doubled on every following one.
+    pub backoff: Duration,
+}
+
+impl Default for FetchOptions {
+    fn default() -> FetchOptions {
+        FetchOptions {
+            base_url: None,
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+            retries: DEFAULT_RETRIES,
+            backoff: Duration::from_millis(DEFAULT_BACKOFF_MILLIS),
+        }
+    }
+}
+
+impl FetchOptions {
+    /// Build the URL for a release given its default base URL and path.
+    pub fn url(&self, default_base_url: &str, path: &str) -> String {
+        let base_url = self.base_url.as_deref().unwrap_or(default_base_url);
+        format!("{}{path}", base_url.trim_end_matches('/'))
+    }
+}
+
+fn is_retryable(status: u16) -> bool {
+    status >= 500 || status == TOO_MANY_REQUESTS_STATUS
+}
+
+/// Send a GET request retrying transport failures, server errors and
+/// throttled responses with exponential backoff.
+///
+/// Responses with a client error status are returned as errors,
+/// any other response (including `304 Not Modified`) is returned as is.
+pub fn fetch(
+    client: &dyn HttpClient,
+    url: &str,
+    headers: &[(String, String)],
+    options: &FetchOptions,
+) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    let request = HttpRequest {
+        url: url.to_string(),
+        headers: headers.to_vec(),
+        timeout: options.timeout,
+    };
+    let mut backoff = options.backoff;
+    let mut attempt = 0;
+
+    loop {
+        let error: Box<dyn std::error::Error> = match client.get(&request) {
+            Ok(response) if !is_retryable(response.status) => {
+                if response.status >= 400 {
+                    return Err(format!("GET {url} failed with status {}", response.status).into());
+                }
+                return Ok(response);
+            }
+            Ok(response) => format!("GET {url} failed with status {}", response.status).into(),
+            Err(e) => e,
+        };
+        if attempt == options.retries {
+            return Err(error);
+        }
+        attempt += 1;
+        thread::sleep(backoff);
+        backoff *= 2;
+    }
+}
+
+/// Write downloaded bytes to a file.
+pub fn write_file(path: &Path, bytes: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut dest = File::create(path)?;
+    dest.write_all(bytes)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    struct StatusClient {
+        statuses: RefCell<Vec<u16>>,
+    }
+
+    impl HttpClient for StatusClient {
+        fn get(&self, _: &HttpRequest) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+            Ok(HttpResponse {
+                status: self.statuses.borrow_mut().remove(0),
+                headers: vec![("ETag".to_string(), "\"v1\"".to_string())],
+                body: b"data".to_vec(),
+            })
+        }
+    }
+
+    fn options(retries: u32) -> FetchOptions {
+        FetchOptions {
+            retries,
+            backoff: Duration::ZERO,
+            ..FetchOptions::default()
+        }
+    }
+
+    #[test]
+    fn fetch_retries_test() {
+        let client = StatusClient {
+            statuses: RefCell::new(vec![503, 429, 200]),
+        };
+        let response = fetch(&client, "http://localhost/", &[], &options(2)).unwrap();
+        assert_eq!(response.body, b"data");
+        assert_eq!(response.header("etag"), Some("\"v1\""));
+
+        let client = StatusClient {
+            statuses: RefCell::new(vec![503, 503]),
+        };
+        assert!(fetch(&client, "http://localhost/", &[], &options(1)).is_err());
+
+        let client = StatusClient {
+            statuses: RefCell::new(vec![404, 200]),
+        };
+        assert!(fetch(&client, "http://localhost/", &[], &options(1)).is_err());
+    }
+
+    #[test]
+    fn url_test() {
+        let mut options = FetchOptions::default();
+        assert_eq!(
+            options.url("https://a.org", "/x?y=1"),
+            "https://a.org/x?y=1"
+        );
+        options.base_url = Some("http://127.0.0.1:8080/".to_string());
+        assert_eq!(
+            options.url("https://a.org", "/x"),
+            "http://127.0.0.1:8080/x"
+        );
+    }
+}
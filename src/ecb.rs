@@ -0,0 +1,501 @@
+use chrono::{NaiveDate, Weekday};
+use roxmltree::{Document, Node};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::source::DataSource;
+use crate::types;
+use crate::types::{BalanceSheet, Concept, ConceptType, ObservationMap, ObservationStatus};
+
+/*
+    Urls for the Eurosystem consolidated weekly financial statement
+    https://www.ecb.europa.eu/press/pr/wfs/html/index.en.html
+    https://data.ecb.europa.eu/data/datasets/ILM
+    https://data-api.ecb.europa.eu/service/data/ILM/W.U2.C..Z5.Z01?format=csvdata
+*/
+/// URL to download the Eurosystem weekly financial statement series as CSV.
+pub const WFS_ECB_URL: &str =
+    "https://data-api.ecb.europa.eu/service/data/ILM/W.U2.C..Z5.Z01?format=csvdata";
+
+/// File name given to the downloaded weekly financial statement CSV.
+pub const WFS_DATA_CSV: &str = "wfs_data.csv";
+
+/// Dataflow identifier of the internal liquidity management (ILM) series.
+pub const ILM_DATAFLOW: &str = "ILM";
+
+pub(crate) const ECB_CURRENCY: &str = "EUR";
+
+const TOTAL_ITEM: &str = "T000000";
+const CAPITAL_ITEM: &str = "L120000";
+// Position of the balance sheet item inside a series key,
+// e.g. ILM.W.U2.C.A010000.Z5.Z01
+const KEY_ITEM_POSITION: usize = 4;
+const KEY_SEPARATOR: char = '.';
+
+const SERIES_TAG: &str = "Series";
+const SERIES_KEY_TAG: &str = "SeriesKey";
+const VALUE_TAG: &str = "Value";
+const OBS_TAG: &str = "Obs";
+const OBS_DIMENSION_TAG: &str = "ObsDimension";
+const OBS_VALUE_TAG: &str = "ObsValue";
+const ATTRIBUTES_TAG: &str = "Attributes";
+
+const KEY_COLUMN: &str = "KEY";
+const TIME_PERIOD_COLUMN: &str = "TIME_PERIOD";
+const OBS_VALUE_COLUMN: &str = "OBS_VALUE";
+const OBS_STATUS_COLUMN: &str = "OBS_STATUS";
+// SDMX observation statuses of values not published: missing (M),
+// not collected (L), holiday (H) and confidential (Q). Any other status,
+// e.g. provisional (P), estimated (E) or break (B), comes with a value.
+const OBS_STATUS_MISSING: [&str; 4] = ["M", "L", "H", "Q"];
+
+/*
+    Balance sheet items of the consolidated weekly financial statement of the Eurosystem.
+
+    Items are listed in the order they are published, parents before their
+    subitems, so the concept tree keeps the statement layout.
+
+    Liability item 12 "Capital and reserves" becomes the capital concept and
+    the total liabilities concept is computed as total (T000000) minus capital.
+*/
+const WFS_ITEMS: [(&str, &str); 43] = [
+    ("A010000", "Assets/Gold and gold receivables"),
+    ("A020000", "Assets/Claims on non-euro area residents denominated in foreign currency"),
+    ("A020100", "Assets/Claims on non-euro area residents denominated in foreign currency/Receivables from the IMF"),
+    ("A020200", "Assets/Claims on non-euro area residents denominated in foreign currency/Balances with banks and security investments, external loans and other external assets"),
+    ("A030000", "Assets/Claims on euro area residents denominated in foreign currency"),
+    ("A040000", "Assets/Claims on non-euro area residents denominated in euro"),
+    ("A040100", "Assets/Claims on non-euro area residents denominated in euro/Balances with banks, security investments and loans"),
+    ("A040200", "Assets/Claims on non-euro area residents denominated in euro/Claims arising from the credit facility under ERM II"),
+    ("A050000", "Assets/Lending to euro area credit institutions related to monetary policy operations denominated in euro"),
+    ("A050100", "Assets/Lending to euro area credit institutions related to monetary policy operations denominated in euro/Main refinancing operations"),
+    ("A050200", "Assets/Lending to euro area credit institutions related to monetary policy operations denominated in euro/Longer-term refinancing operations"),
+    ("A050300", "Assets/Lending to euro area credit institutions related to monetary policy operations denominated in euro/Fine-tuning reverse operations"),
+    ("A050400", "Assets/Lending to euro area credit institutions related to monetary policy operations denominated in euro/Structural reverse operations"),
+    ("A050500", "Assets/Lending to euro area credit institutions related to monetary policy operations denominated in euro/Marginal lending facility"),
+    ("A050600", "Assets/Lending to euro area credit institutions related to monetary policy operations denominated in euro/Credits related to margin calls"),
+    ("A060000", "Assets/Other claims on euro area credit institutions denominated in euro"),
+    ("A070000", "Assets/Securities of euro area residents denominated in euro"),
+    ("A070100", "Assets/Securities of euro area residents denominated in euro/Securities held for monetary policy purposes"),
+    ("A070200", "Assets/Securities of euro area residents denominated in euro/Other securities"),
+    ("A080000", "Assets/General government debt denominated in euro"),
+    ("A090000", "Assets/Other assets"),
+    ("L010000", "Liabilities/Banknotes in circulation"),
+    ("L020000", "Liabilities/Liabilities to euro area credit institutions related to monetary policy operations denominated in euro"),
+    ("L020100", "Liabilities/Liabilities to euro area credit institutions related to monetary policy operations denominated in euro/Current accounts (covering the minimum reserve system)"),
+    ("L020200", "Liabilities/Liabilities to euro area credit institutions related to monetary policy operations denominated in euro/Deposit facility"),
+    ("L020300", "Liabilities/Liabilities to euro area credit institutions related to monetary policy operations denominated in euro/Fixed-term deposits"),
+    ("L020400", "Liabilities/Liabilities to euro area credit institutions related to monetary policy operations denominated in euro/Fine-tuning reverse operations"),
+    ("L020500", "Liabilities/Liabilities to euro area credit institutions related to monetary policy operations denominated in euro/Deposits related to margin calls"),
+    ("L030000", "Liabilities/Other liabilities to euro area credit institutions denominated in euro"),
+    ("L040000", "Liabilities/Debt certificates issued"),
+    ("L050000", "Liabilities/Liabilities to other euro area residents denominated in euro"),
+    ("L050100", "Liabilities/Liabilities to other euro area residents denominated in euro/General government"),
+    ("L050200", "Liabilities/Liabilities to other euro area residents denominated in euro/Other liabilities"),
+    ("L060000", "Liabilities/Liabilities to non-euro area residents denominated in euro"),
+    ("L070000", "Liabilities/Liabilities to euro area residents denominated in foreign currency"),
+    ("L080000", "Liabilities/Liabilities to non-euro area residents denominated in foreign currency"),
+    ("L080100", "Liabilities/Liabilities to non-euro area residents denominated in foreign currency/Deposits, balances and other liabilities"),
+    ("L080200", "Liabilities/Liabilities to non-euro area residents denominated in foreign currency/Liabilities arising from the credit facility under ERM II"),
+    ("L090000", "Liabilities/Counterpart of special drawing rights allocated by the IMF"),
+    ("L100000", "Liabilities/Other liabilities"),
+    ("L110000", "Liabilities/Revaluation accounts"),
+    (CAPITAL_ITEM, types::CAPITAL_PATH),
+    (TOTAL_ITEM, types::ASSETS_PATH),
+];
+
+struct WfsObservation {
+    series_key: String,
+    date: NaiveDate,
+    value: i64,
+    status: ObservationStatus,
+}
+
+fn series_item(series_key: &str) -> Option<&str> {
+    series_key.split(KEY_SEPARATOR).nth(KEY_ITEM_POSITION)
+}
+
+fn item_path(item: &str) -> Option<&'static str> {
+    WFS_ITEMS.iter().find(|(i, _)| *i == item).map(|(_, p)| *p)
+}
+
+fn concept_type(path: &str) -> ConceptType {
+    ConceptType::from_path(path).unwrap_or(ConceptType::Capital)
+}
+
+/// Weekly statements are published as of Friday, periods can be either
+/// a plain date or an ISO week like "2020-W22".
+fn parse_time_period(period: &str) -> Result<NaiveDate, Box<dyn std::error::Error>> {
+    if let Some((year, week)) = period.split_once("-W") {
+        return NaiveDate::from_isoywd_opt(year.parse()?, week.parse()?, Weekday::Fri)
+            .ok_or_else(|| format!("invalid weekly time period '{period}'").into());
+    }
+    Ok(NaiveDate::parse_from_str(period, "%Y-%m-%d")?)
+}
+
+fn parse_obs_value(value: Option<&str>, status: Option<&str>) -> (i64, ObservationStatus) {
+    if status.is_some_and(|status| OBS_STATUS_MISSING.contains(&status.trim())) {
+        return (0, ObservationStatus::NotAvailable);
+    }
+    match value.map(|v| v.trim().parse::<f64>()) {
+        Some(Ok(v)) => (v.round() as i64, ObservationStatus::Available),
+        _ => (0, ObservationStatus::NotAvailable),
+    }
+}
+
+fn observations_to_balance_sheets(
+    observations: &[WfsObservation],
+) -> Result<ObservationMap, Box<dyn std::error::Error>> {
+    let mut series: HashMap<&str, &str> = HashMap::new();
+    for observation in observations {
+        if let Some(item) = series_item(&observation.series_key) {
+            series.insert(item, &observation.series_key);
+        }
+    }
+    if !series.contains_key(TOTAL_ITEM) {
+        return Err(format!("weekly financial statement without total series {TOTAL_ITEM}").into());
+    }
+
+    let mut assets = Concept::new(types::ASSETS_PATH, series[TOTAL_ITEM]);
+    assets.currency = ECB_CURRENCY.to_string();
+    let mut liabilities = Concept::new(types::LIABILITIES_PATH, types::UNDEFINED_SERIES_NAME);
+    liabilities.currency = ECB_CURRENCY.to_string();
+    let mut capital = Concept::new(
+        types::CAPITAL_PATH,
+        series
+            .get(CAPITAL_ITEM)
+            .copied()
+            .unwrap_or(types::UNDEFINED_SERIES_NAME),
+    );
+    capital.currency = ECB_CURRENCY.to_string();
+
+    for (item, path) in WFS_ITEMS.iter() {
+        let series_key = match series.get(item) {
+            Some(series_key) if *item != TOTAL_ITEM && *item != CAPITAL_ITEM => series_key,
+            _ => continue,
+        };
+        match concept_type(path) {
+            ConceptType::Assets => assets.insert_concept(path, series_key),
+            ConceptType::Liabilities => liabilities.insert_concept(path, series_key),
+            ConceptType::Capital => capital.insert_concept(path, series_key),
+        }
+    }
+
+    let bs_template = BalanceSheet::new(assets, liabilities, capital);
+    let mut obs: ObservationMap = BTreeMap::new();
+    for observation in observations {
+        let path = match series_item(&observation.series_key).and_then(item_path) {
+            Some(path) => path,
+            None => continue,
+        };
+        obs.entry(observation.date)
+            .or_insert_with(|| bs_template.clone())
+            .get_concept_mut(&concept_type(path))
+            .update_concept_observation(path, observation.value, observation.status);
+    }
+
+    // The statement only publishes a grand total for both sides, the
+    // liabilities are derived from it.
+    for bs in obs.values_mut() {
+        let total = bs.get_concept(&ConceptType::Assets);
+        let status = if total.status.has_value() {
+            ObservationStatus::Derived
+        } else {
+            total.status
+        };
+        let value = total.value - bs.get_concept(&ConceptType::Capital).value;
+        let liabilities = bs.get_concept_mut(&ConceptType::Liabilities);
+        liabilities.value = value;
+        liabilities.status = status;
+    }
+
+    Ok(obs)
+}
+
+fn get_children_node_elements<'a>(node: &Node<'a, 'a>, tag: &str) -> Vec<Node<'a, 'a>> {
+    node.children()
+        .filter(|n| n.is_element() && n.tag_name().name() == tag)
+        .collect()
+}
+
+fn get_component_value<'a>(node: &Node<'a, 'a>, id: &str) -> Option<&'a str> {
+    get_children_node_elements(node, VALUE_TAG)
+        .into_iter()
+        .find(|v| v.attribute("id") == Some(id))
+        .and_then(|v| v.attribute("value"))
+}
+
+/// Parse the Eurosystem weekly financial statement from an ILM SDMX-ML 2.1
+/// generic data message to return an ordered map with a balance sheet
+/// for each week.
+pub fn parse_wfs_sdmx(text: &str) -> Result<ObservationMap, Box<dyn std::error::Error>> {
+    let doc = Document::parse(text)?;
+    let mut observations = Vec::new();
+
+    for serie in doc
+        .descendants()
+        .filter(|n| n.is_element() && n.tag_name().name() == SERIES_TAG)
+    {
+        let series_key = match get_children_node_elements(&serie, SERIES_KEY_TAG).first() {
+            Some(key) => get_children_node_elements(key, VALUE_TAG)
+                .iter()
+                .filter_map(|v| v.attribute("value"))
+                .fold(ILM_DATAFLOW.to_string(), |k, v| {
+                    format!("{k}{KEY_SEPARATOR}{v}")
+                }),
+            None => continue,
+        };
+
+        for obs in get_children_node_elements(&serie, OBS_TAG) {
+            let period = get_children_node_elements(&obs, OBS_DIMENSION_TAG)
+                .first()
+                .and_then(|d| d.attribute("value"))
+                .ok_or("SDMX observation without time period")?;
+            let value = get_children_node_elements(&obs, OBS_VALUE_TAG)
+                .first()
+                .and_then(|v| v.attribute("value"));
+            let status = get_children_node_elements(&obs, ATTRIBUTES_TAG)
+                .first()
+                .and_then(|a| get_component_value(a, OBS_STATUS_COLUMN));
+            let (value, status) = parse_obs_value(value, status);
+            observations.push(WfsObservation {
+                series_key: series_key.clone(),
+                date: parse_time_period(period)?,
+                value,
+                status,
+            });
+        }
+    }
+
+    observations_to_balance_sheets(&observations)
+}
+
+fn split_csv_records(text: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => record.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+/// Parse the Eurosystem weekly financial statement from an ILM CSV export
+/// (as returned by the ECB data API with `format=csvdata`) to return an
+/// ordered map with a balance sheet for each week.
+pub fn parse_wfs_csv(text: &str) -> Result<ObservationMap, Box<dyn std::error::Error>> {
+    let mut records = split_csv_records(text).into_iter();
+    let header = records
+        .next()
+        .ok_or("empty CSV weekly financial statement")?;
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|h| h.trim_start_matches('\u{feff}') == name)
+            .ok_or_else(|| format!("CSV weekly financial statement without {name} column"))
+    };
+    let key_column = column(KEY_COLUMN)?;
+    let period_column = column(TIME_PERIOD_COLUMN)?;
+    let value_column = column(OBS_VALUE_COLUMN)?;
+    let status_column = column(OBS_STATUS_COLUMN).ok();
+
+    let mut observations = Vec::new();
+    for record in records.filter(|r| r.len() > 1) {
+        let field = |i: usize| record.get(i).map(|f| f.as_str()).filter(|f| !f.is_empty());
+        let (value, status) = parse_obs_value(field(value_column), status_column.and_then(field));
+        observations.push(WfsObservation {
+            series_key: field(key_column)
+                .ok_or("CSV record without series key")?
+                .to_string(),
+            date: parse_time_period(field(period_column).ok_or("CSV record without time period")?)?,
+            value,
+            status,
+        });
+    }
+
+    observations_to_balance_sheets(&observations)
+}
+
+/// Parse the Eurosystem weekly financial statement either from its SDMX-ML
+/// or its CSV export, depending on the content of the text.
+pub fn parse_wfs_data(text: &str) -> Result<ObservationMap, Box<dyn std::error::Error>> {
+    if text.trim_start().starts_with('<') {
+        parse_wfs_sdmx(text)
+    } else {
+        parse_wfs_csv(text)
+    }
+}
+
+/// Eurosystem consolidated weekly financial statement release.
+pub struct WfsSource;
+
+impl DataSource for WfsSource {
+    fn id(&self) -> &'static str {
+        "ecb"
+    }
+
+    fn description(&self) -> &'static str {
+        "Eurosystem consolidated weekly financial statement"
+    }
+
+    fn urls(&self) -> &'static [&'static str] {
+        &[WFS_ECB_URL]
+    }
+
+    fn archive_members(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn data_file(&self) -> &'static str {
+        WFS_DATA_CSV
+    }
+
+    fn statements(&self) -> &'static [ConceptType] {
+        &ConceptType::ALL
+    }
+
+    fn parse(&self, text: &str) -> Result<ObservationMap, Box<dyn std::error::Error>> {
+        parse_wfs_data(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WFS_CSV: &str = "KEY,FREQ,REF_AREA,BS_REP_SECTOR,BS_ITEM,TIME_PERIOD,OBS_VALUE,OBS_STATUS,TITLE\r
+ILM.W.U2.C.T000000.Z5.Z01,W,U2,C,T000000,2020-W22,\"6401367.5\",A,\"Total assets/liabilities, Eurosystem\"\r
+ILM.W.U2.C.A010000.Z5.Z01,W,U2,C,A010000,2020-W22,545810.2,A,Gold and gold receivables\r
+ILM.W.U2.C.A050000.Z5.Z01,W,U2,C,A050000,2020-W22,1000.0,A,Lending\r
+ILM.W.U2.C.A050100.Z5.Z01,W,U2,C,A050100,2020-W22,600.0,A,Main refinancing operations\r
+ILM.W.U2.C.A050200.Z5.Z01,W,U2,C,A050200,2020-W22,400.0,A,Longer-term refinancing operations\r
+ILM.W.U2.C.L120000.Z5.Z01,W,U2,C,L120000,2020-W22,108767.0,A,Capital and reserves\r
+ILM.W.U2.C.L010000.Z5.Z01,W,U2,C,L010000,2020-W22,1364430.1,P,Banknotes in circulation\r
+";
+
+    const WFS_SDMX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<message:GenericData xmlns:message="http://www.sdmx.org/resources/sdmxml/schemas/v2_1/message" xmlns:generic="http://www.sdmx.org/resources/sdmxml/schemas/v2_1/data/generic">
+<message:DataSet>
+<generic:Series>
+<generic:SeriesKey><generic:Value id="FREQ" value="W"/><generic:Value id="REF_AREA" value="U2"/><generic:Value id="BS_REP_SECTOR" value="C"/><generic:Value id="BS_ITEM" value="T000000"/><generic:Value id="CURRENCY_TRANS" value="Z5"/><generic:Value id="UNIT" value="Z01"/></generic:SeriesKey>
+<generic:Obs><generic:ObsDimension value="2020-05-29"/><generic:ObsValue value="6401367"/><generic:Attributes><generic:Value id="OBS_STATUS" value="A"/></generic:Attributes></generic:Obs>
+</generic:Series>
+<generic:Series>
+<generic:SeriesKey><generic:Value id="FREQ" value="W"/><generic:Value id="REF_AREA" value="U2"/><generic:Value id="BS_REP_SECTOR" value="C"/><generic:Value id="BS_ITEM" value="L120000"/><generic:Value id="CURRENCY_TRANS" value="Z5"/><generic:Value id="UNIT" value="Z01"/></generic:SeriesKey>
+<generic:Obs><generic:ObsDimension value="2020-05-29"/><generic:ObsValue value="108767"/><generic:Attributes><generic:Value id="OBS_STATUS" value="A"/></generic:Attributes></generic:Obs>
+</generic:Series>
+</message:DataSet>
+</message:GenericData>"#;
+
+    #[test]
+    fn parse_time_period_test() {
+        let friday = NaiveDate::from_ymd_opt(2020, 5, 29).unwrap();
+        assert_eq!(friday, parse_time_period("2020-W22").unwrap());
+        assert_eq!(friday, parse_time_period("2020-05-29").unwrap());
+        assert!(parse_time_period("2020-W99").is_err());
+    }
+
+    #[test]
+    fn parse_wfs_csv_test() {
+        let obs = parse_wfs_csv(WFS_CSV).unwrap();
+        let bs = obs
+            .get(&NaiveDate::from_ymd_opt(2020, 5, 29).unwrap())
+            .unwrap();
+        let assets = bs.get_concept(&ConceptType::Assets);
+        assert_eq!(assets.value, 6401368);
+        assert_eq!(assets.series_name, "ILM.W.U2.C.T000000.Z5.Z01");
+        assert!(assets.iter().all(|c| c.currency == ECB_CURRENCY));
+        let lending: Vec<i64> = assets
+            .iter()
+            .filter(|c| c.path.starts_with("Assets/Lending"))
+            .map(|c| c.value)
+            .collect();
+        assert_eq!(lending, vec![1000, 600, 400]);
+        assert_eq!(bs.get_concept(&ConceptType::Capital).value, 108767);
+        assert_eq!(
+            bs.get_concept(&ConceptType::Liabilities).value,
+            6401368 - 108767
+        );
+        assert_eq!(
+            bs.get_concept(&ConceptType::Liabilities).status,
+            ObservationStatus::Derived
+        );
+        assert_eq!(
+            bs.get_concept(&ConceptType::Liabilities)
+                .iter()
+                .last()
+                .unwrap()
+                .value,
+            1364430
+        );
+    }
+
+    #[test]
+    fn parse_obs_value_test() {
+        for status in [None, Some("A"), Some("P"), Some("E"), Some("B")] {
+            assert_eq!(
+                parse_obs_value(Some("10.4"), status),
+                (10, ObservationStatus::Available)
+            );
+        }
+        assert_eq!(
+            parse_obs_value(Some("10.4"), Some("M")),
+            (0, ObservationStatus::NotAvailable)
+        );
+        assert_eq!(
+            parse_obs_value(None, Some("P")),
+            (0, ObservationStatus::NotAvailable)
+        );
+        assert_eq!(
+            parse_obs_value(Some("n/a"), Some("A")),
+            (0, ObservationStatus::NotAvailable)
+        );
+    }
+
+    #[test]
+    fn parse_wfs_sdmx_test() {
+        let obs = parse_wfs_data(WFS_SDMX).unwrap();
+        let bs = obs
+            .get(&NaiveDate::from_ymd_opt(2020, 5, 29).unwrap())
+            .unwrap();
+        assert_eq!(bs.get_concept(&ConceptType::Assets).value, 6401367);
+        assert_eq!(
+            bs.get_concept(&ConceptType::Capital).series_name,
+            "ILM.W.U2.C.L120000.Z5.Z01"
+        );
+        assert_eq!(bs.get_concept(&ConceptType::Liabilities).value, 6292600);
+
+        let missing = WFS_SDMX.replace(
+            r#"<generic:ObsValue value="6401367"/><generic:Attributes><generic:Value id="OBS_STATUS" value="A"/>"#,
+            r#"<generic:ObsValue value="6401367"/><generic:Attributes><generic:Value id="OBS_STATUS" value="M"/>"#,
+        );
+        let obs = parse_wfs_data(&missing).unwrap();
+        assert_eq!(
+            obs.values()
+                .next()
+                .unwrap()
+                .get_concept(&ConceptType::Liabilities)
+                .status,
+            ObservationStatus::NotAvailable
+        );
+    }
+}
@@ -0,0 +1,14 @@
+/// Provides Arrow record batch conversion and Arrow IPC export of observations.
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub(crate) mod columns;
+/// Provides RFC 4180 compliant CSV export of observations.
+pub mod csv;
+/// Provides long (tidy) format export of observations as CSV and JSON Lines.
+pub mod long;
+/// Provides Apache Parquet export of observations.
+#[cfg(feature = "parquet")]
+pub mod parquet;
+/// Provides xlsx workbook export of observations.
+#[cfg(feature = "xlsx")]
+pub mod xlsx;
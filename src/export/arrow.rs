@@ -0,0 +1,194 @@
+use arrow_array::{
+    ArrayRef, BooleanArray, Date32Array, Int32Array, Int64Array, RecordBatch, StringArray,
+};
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::{DataType, Field, Schema};
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::Arc;
+
+use crate::export::columns::{
+    currency, days_since_epoch, leaf_columns, unit, CURRENCY_KEY, UNIT_KEY,
+};
+use crate::export::long::{long_records, LONG_COLUMNS};
+use crate::types::{ConceptType, ObservationMap};
+
+const DATE_COLUMN: &str = "date";
+const VALUE_COLUMN: &str = "value";
+const STATUS_COLUMN: &str = "status";
+const PATH_KEY: &str = "path";
+const SERIES_NAME_KEY: &str = "series_name";
+
+fn unit_metadata(currency: &str) -> HashMap<String, String> {
+    HashMap::from([
+        (UNIT_KEY.to_string(), unit(currency)),
+        (CURRENCY_KEY.to_string(), currency.to_string()),
+    ])
+}
+
+fn date_array(obs: &ObservationMap) -> ArrayRef {
+    Arc::new(Date32Array::from_iter_values(
+        obs.keys().map(days_since_epoch),
+    ))
+}
+
+/// Convert the observations of a statement into a record batch in wide
+/// format: a Date32 column and one Int64 column per leaf concept named
+/// by its path.
+///
+/// Every concept column carries its series name as field metadata and
+/// the schema carries the unit and currency of the values.
+pub fn to_record_batch(
+    obs: &ObservationMap,
+    ctype: &ConceptType,
+) -> Result<RecordBatch, Box<dyn std::error::Error>> {
+    let mut fields = vec![Field::new(DATE_COLUMN, DataType::Date32, false)];
+    let mut arrays = vec![date_array(obs)];
+    for (concept, values) in leaf_columns(obs, ctype) {
+        fields.push(
+            Field::new(&concept.path, DataType::Int64, false).with_metadata(HashMap::from([(
+                SERIES_NAME_KEY.to_string(),
+                concept.series_name.clone(),
+            )])),
+        );
+        arrays.push(Arc::new(Int64Array::from(values)));
+    }
+    let schema = Schema::new(fields).with_metadata(unit_metadata(currency(obs, &[*ctype])));
+    Ok(RecordBatch::try_new(Arc::new(schema), arrays)?)
+}
+
+/// Convert the observations of the given statements into a record batch
+/// in long format, with the same columns as the long CSV export.
+pub fn to_long_record_batch(
+    obs: &ObservationMap,
+    statements: &[ConceptType],
+) -> Result<RecordBatch, Box<dyn std::error::Error>> {
+    let records: Vec<_> = long_records(obs, statements).collect();
+    let arrays: Vec<ArrayRef> = vec![
+        Arc::new(Date32Array::from_iter_values(
+            records.iter().map(|r| days_since_epoch(&r.date)),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            records.iter().map(|r| r.statement.to_string()),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            records.iter().map(|r| r.path),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            records.iter().map(|r| r.series_name),
+        )),
+        Arc::new(Int32Array::from_iter_values(
+            records.iter().map(|r| r.depth as i32),
+        )),
+        Arc::new(BooleanArray::from_iter(
+            records.iter().map(|r| Some(r.is_leaf)),
+        )),
+        Arc::new(Int64Array::from_iter_values(
+            records.iter().map(|r| r.value),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            records.iter().map(|r| r.status.to_string()),
+        )),
+    ];
+    let fields: Vec<Field> = LONG_COLUMNS
+        .iter()
+        .zip(&arrays)
+        .map(|(name, array)| Field::new(*name, array.data_type().clone(), false))
+        .collect();
+    let schema = Schema::new(fields).with_metadata(unit_metadata(currency(obs, statements)));
+    Ok(RecordBatch::try_new(Arc::new(schema), arrays)?)
+}
+
+/// Convert the time series of a single concept into a record batch with
+/// date, value and status columns.
+///
+/// The concept path and series name are stored as schema metadata.
+pub fn series_to_record_batch(
+    obs: &ObservationMap,
+    path: &str,
+) -> Result<RecordBatch, Box<dyn std::error::Error>> {
+    let ctype = ConceptType::from_path(path).ok_or_else(|| format!("unknown concept: {path}"))?;
+    let concepts = obs
+        .values()
+        .map(|bs| bs.get(path))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| format!("unknown concept: {path}"))?;
+
+    let mut metadata = unit_metadata(currency(obs, &[ctype]));
+    metadata.insert(PATH_KEY.to_string(), path.to_string());
+    if let Some(concept) = concepts.first() {
+        metadata.insert(SERIES_NAME_KEY.to_string(), concept.series_name.clone());
+    }
+    let schema = Schema::new(vec![
+        Field::new(DATE_COLUMN, DataType::Date32, false),
+        Field::new(VALUE_COLUMN, DataType::Int64, false),
+        Field::new(STATUS_COLUMN, DataType::Utf8, false),
+    ])
+    .with_metadata(metadata);
+    let arrays: Vec<ArrayRef> = vec![
+        date_array(obs),
+        Arc::new(Int64Array::from_iter_values(
+            concepts.iter().map(|c| c.value),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            concepts.iter().map(|c| c.status.to_string()),
+        )),
+    ];
+    Ok(RecordBatch::try_new(Arc::new(schema), arrays)?)
+}
+
+/// Write record batches sharing the same schema as an Arrow IPC file.
+pub fn write_ipc<W: Write>(
+    writer: W,
+    batches: &[RecordBatch],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let schema = batches
+        .first()
+        .map(|b| b.schema())
+        .ok_or("no record batches to write")?;
+    let mut writer = FileWriter::try_new(writer, &schema)?;
+    for batch in batches {
+        writer.write(batch)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fed::parse_h41_data;
+    use arrow_array::Array;
+    use arrow_ipc::reader::FileReader;
+    use std::fs;
+    use std::io::Cursor;
+
+    #[test]
+    fn record_batch_test() {
+        let text = fs::read_to_string("tests/data/FRB_H41_2010.xml").unwrap();
+        let obs = parse_h41_data(&text).unwrap();
+
+        let batch = to_record_batch(&obs, &ConceptType::Capital).unwrap();
+        assert_eq!(batch.num_rows(), obs.len());
+        let schema = batch.schema();
+        assert_eq!(schema.field(1).name(), "Capital/Capital paid in");
+        assert_eq!(schema.metadata()[UNIT_KEY], "millions of USD");
+        let values = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(values.value(0), 25651);
+
+        let series = series_to_record_batch(&obs, "Capital/Capital paid in").unwrap();
+        assert_eq!(series.column(1).len(), obs.len());
+        assert!(series_to_record_batch(&obs, "Capital/Unknown").is_err());
+
+        let long = to_long_record_batch(&obs, &[ConceptType::Capital]).unwrap();
+        let mut out = Vec::new();
+        write_ipc(&mut out, std::slice::from_ref(&long)).unwrap();
+        let reader = FileReader::try_new(Cursor::new(out), None).unwrap();
+        let batches: Vec<RecordBatch> = reader.map(|b| b.unwrap()).collect();
+        assert_eq!(batches, vec![long]);
+    }
+}
@@ -0,0 +1,71 @@
+use chrono::NaiveDate;
+
+#[cfg(any(feature = "arrow", feature = "parquet"))]
+use crate::types::Concept;
+#[cfg(any(feature = "arrow", feature = "parquet", feature = "xlsx"))]
+use crate::types::{ConceptType, ObservationMap};
+
+#[cfg(any(feature = "arrow", feature = "parquet", feature = "xlsx"))]
+const UNIT_MULTIPLIER: &str = "millions";
+
+/// Metadata key of the unit of the values, see [`unit`].
+#[cfg(any(feature = "arrow", feature = "parquet"))]
+pub(crate) const UNIT_KEY: &str = "unit";
+
+/// Metadata key of the currency of the values.
+#[cfg(any(feature = "arrow", feature = "parquet"))]
+pub(crate) const CURRENCY_KEY: &str = "currency";
+
+/// Days elapsed since the unix epoch, as stored by DATE columns.
+pub(crate) fn days_since_epoch(date: &NaiveDate) -> i32 {
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+    (*date - epoch).num_days() as i32
+}
+
+/// Leaf concepts of a statement along with their value for every date.
+#[cfg(any(feature = "arrow", feature = "parquet"))]
+pub(crate) fn leaf_columns<'a>(
+    obs: &'a ObservationMap,
+    ctype: &ConceptType,
+) -> Vec<(&'a Concept, Vec<i64>)> {
+    let mut columns: Vec<(&Concept, Vec<i64>)> = match obs.values().next() {
+        Some(bs) => bs
+            .get_concept(ctype)
+            .iter()
+            .filter(|c| c.is_leaf())
+            .map(|c| (c, Vec::with_capacity(obs.len())))
+            .collect(),
+        None => return Vec::new(),
+    };
+    for bs in obs.values() {
+        let leaves = bs.get_concept(ctype).iter().filter(|c| c.is_leaf());
+        for ((_, values), c) in columns.iter_mut().zip(leaves) {
+            values.push(c.value);
+        }
+    }
+    columns
+}
+
+/// Currency of the first of the given statements stating one.
+#[cfg(any(feature = "arrow", feature = "parquet", feature = "xlsx"))]
+pub(crate) fn currency<'a>(obs: &'a ObservationMap, statements: &[ConceptType]) -> &'a str {
+    obs.values()
+        .next()
+        .and_then(|bs| {
+            statements
+                .iter()
+                .map(|ctype| bs.get_concept(ctype).currency.as_str())
+                .find(|c| !c.is_empty())
+        })
+        .unwrap_or_default()
+}
+
+/// Unit of the values expressed in the given currency, e.g. "millions of USD".
+#[cfg(any(feature = "arrow", feature = "parquet", feature = "xlsx"))]
+pub(crate) fn unit(currency: &str) -> String {
+    if currency.is_empty() {
+        UNIT_MULTIPLIER.to_string()
+    } else {
+        format!("{UNIT_MULTIPLIER} of {currency}")
+    }
+}
@@ -0,0 +1,215 @@
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crate::types::{Concept, ConceptType, ObservationMap};
+
+const DATE_COLUMN: &str = "date";
+const QUOTE: char = '"';
+
+/// Text used as header of every concept column.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CsvHeader {
+    /// concept name, ambiguous if two leaves share it under different parents.
+    #[default]
+    Name,
+    /// full concept path.
+    Path,
+    /// name of the series the concept was extracted from.
+    SeriesName,
+}
+
+impl FromStr for CsvHeader {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<CsvHeader, Self::Err> {
+        match s {
+            "name" => Ok(CsvHeader::Name),
+            "path" => Ok(CsvHeader::Path),
+            "series" => Ok(CsvHeader::SeriesName),
+            _ => Err(format!("unknown CSV header: {s}")),
+        }
+    }
+}
+
+/// Options controlling the CSV output.
+#[derive(Clone, Debug)]
+pub struct CsvOptions {
+    /// field delimiter.
+    pub delimiter: char,
+    /// header of the concept columns.
+    pub header: CsvHeader,
+    /// terminate records with CRLF, as RFC 4180 does, instead of LF.
+    pub crlf: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> CsvOptions {
+        CsvOptions {
+            delimiter: ',',
+            header: CsvHeader::default(),
+            crlf: false,
+        }
+    }
+}
+
+/// Quote a field if it contains the delimiter, a quote or a line break,
+/// doubling any embedded quote as required by RFC 4180.
+pub fn escape_field(field: &str, delimiter: char) -> Cow<'_, str> {
+    if field.contains([delimiter, QUOTE, '\r', '\n']) {
+        Cow::Owned(format!("{QUOTE}{}{QUOTE}", field.replace(QUOTE, "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Check the delimiter can separate RFC 4180 fields, i.e. it is not a
+/// quote or a line break.
+pub fn check_delimiter(delimiter: char) -> io::Result<()> {
+    if matches!(delimiter, QUOTE | '\r' | '\n') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid CSV delimiter: {delimiter:?}"),
+        ));
+    }
+    Ok(())
+}
+
+/// Write a CSV record terminated by LF, or CRLF if `options.crlf` is set,
+/// failing before writing anything if the delimiter is not valid, see
+/// [`check_delimiter`].
+pub fn write_record<W: Write, S: AsRef<str>>(
+    writer: &mut W,
+    fields: &[S],
+    options: &CsvOptions,
+) -> io::Result<()> {
+    let delimiter = options.delimiter;
+    check_delimiter(delimiter)?;
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            write!(writer, "{delimiter}")?;
+        }
+        writer.write_all(escape_field(field.as_ref(), delimiter).as_bytes())?;
+    }
+    writer.write_all(if options.crlf { b"\r\n" } else { b"\n" })
+}
+
+fn header(c: &Concept, header: CsvHeader) -> &str {
+    match header {
+        CsvHeader::Name => c.name(),
+        CsvHeader::Path => &c.path,
+        CsvHeader::SeriesName => &c.series_name,
+    }
+}
+
+/// Write the observations of a statement in wide format: one row per
+/// date and one column per leaf concept.
+pub fn write_csv<W: Write>(
+    writer: &mut W,
+    obs: &ObservationMap,
+    ctype: &ConceptType,
+    options: &CsvOptions,
+) -> io::Result<()> {
+    let mut fields = vec![DATE_COLUMN.to_string()];
+    if let Some(bs) = obs.values().next() {
+        fields.extend(
+            bs.get_concept(ctype)
+                .iter()
+                .filter(|c| c.is_leaf())
+                .map(|c| header(c, options.header).to_string()),
+        );
+    }
+    write_record(writer, &fields, options)?;
+
+    for (date, bs) in obs {
+        fields.clear();
+        fields.push(date.to_string());
+        fields.extend(
+            bs.get_concept(ctype)
+                .iter()
+                .filter(|c| c.is_leaf())
+                .map(|c| c.value.to_string()),
+        );
+        write_record(writer, &fields, options)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::BalanceSheet;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn escape_field_test() {
+        assert_eq!(escape_field("Coin", ','), "Coin");
+        assert_eq!(escape_field("Notes, nominal", ','), "\"Notes, nominal\"");
+        assert_eq!(escape_field("Notes, nominal", ';'), "Notes, nominal");
+        assert_eq!(escape_field("the \"LLC\"", ','), "\"the \"\"LLC\"\"\"");
+        assert_eq!(escape_field("two\nlines", ','), "\"two\nlines\"");
+        assert!(check_delimiter(';').is_ok());
+        for delimiter in ['"', '\r', '\n'] {
+            let options = CsvOptions {
+                delimiter,
+                ..CsvOptions::default()
+            };
+            let mut out = Vec::new();
+            assert!(write_record(&mut out, &["a", "b"], &options).is_err());
+            assert!(out.is_empty());
+        }
+    }
+
+    #[test]
+    fn write_csv_test() {
+        let mut liabilities = Concept::new("Liabilities", "L");
+        liabilities.insert_concept("Liabilities/Deposits/Other", "L1");
+        liabilities.insert_concept("Liabilities/Reverse repurchase agreements/Other", "L2");
+        liabilities.update_concept_value("Liabilities/Deposits/Other", 7);
+        let bs = BalanceSheet::new(
+            Concept::new("Assets", "A"),
+            liabilities,
+            Concept::new("Capital", "C"),
+        );
+        let mut obs = ObservationMap::new();
+        obs.insert(NaiveDate::from_ymd_opt(2020, 5, 27).unwrap(), bs);
+
+        let mut out = Vec::new();
+        write_csv(
+            &mut out,
+            &obs,
+            &ConceptType::Liabilities,
+            &CsvOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "date,Other,Other\n2020-05-27,7,0\n"
+        );
+
+        let options = CsvOptions {
+            header: CsvHeader::Path,
+            crlf: true,
+            ..CsvOptions::default()
+        };
+        let mut out = Vec::new();
+        write_csv(&mut out, &obs, &ConceptType::Liabilities, &options).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "date,Liabilities/Deposits/Other,Liabilities/Reverse repurchase agreements/Other\r\n\
+             2020-05-27,7,0\r\n"
+        );
+
+        let options = CsvOptions {
+            delimiter: ';',
+            header: CsvHeader::SeriesName,
+            crlf: false,
+        };
+        let mut out = Vec::new();
+        write_csv(&mut out, &obs, &ConceptType::Liabilities, &options).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "date;L1;L2\n2020-05-27;7;0\n"
+        );
+    }
+}
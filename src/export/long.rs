@@ -0,0 +1,146 @@
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::io::{self, Write};
+
+use crate::export::csv::{write_record, CsvOptions};
+use crate::types::{ConceptType, ObservationMap, ObservationStatus};
+
+pub(crate) const LONG_COLUMNS: [&str; 8] = [
+    "date",
+    "statement",
+    "path",
+    "series_name",
+    "depth",
+    "is_leaf",
+    "value",
+    "status",
+];
+
+/// Observation of a single concept at a date, including intermediate concepts.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct LongRecord<'a> {
+    /// observation date.
+    pub date: NaiveDate,
+    /// statement holding the concept.
+    pub statement: ConceptType,
+    /// full concept path.
+    pub path: &'a str,
+    /// series the concept was extracted from.
+    pub series_name: &'a str,
+    /// depth of the concept, 0 for the statement root.
+    pub depth: usize,
+    /// true if the concept has no children.
+    pub is_leaf: bool,
+    /// concept value.
+    pub value: i64,
+    /// status of the concept value.
+    pub status: ObservationStatus,
+}
+
+/// Iterate over the observations of the given statements in long format:
+/// one record per date and concept, ordered by date, statement and
+/// concept tree order.
+pub fn long_records<'a>(
+    obs: &'a ObservationMap,
+    statements: &'a [ConceptType],
+) -> impl Iterator<Item = LongRecord<'a>> + 'a {
+    obs.iter().flat_map(move |(date, bs)| {
+        statements.iter().flat_map(move |ctype| {
+            bs.get_concept(ctype).iter().map(move |c| LongRecord {
+                date: *date,
+                statement: *ctype,
+                path: &c.path,
+                series_name: &c.series_name,
+                depth: c.depth(),
+                is_leaf: c.is_leaf(),
+                value: c.value,
+                status: c.status,
+            })
+        })
+    })
+}
+
+/// Write the observations of the given statements as a long format CSV.
+pub fn write_long_csv<W: Write>(
+    writer: &mut W,
+    obs: &ObservationMap,
+    statements: &[ConceptType],
+    options: &CsvOptions,
+) -> io::Result<()> {
+    write_record(writer, &LONG_COLUMNS, options)?;
+    for record in long_records(obs, statements) {
+        let fields = [
+            record.date.to_string(),
+            record.statement.to_string(),
+            record.path.to_string(),
+            record.series_name.to_string(),
+            record.depth.to_string(),
+            record.is_leaf.to_string(),
+            record.value.to_string(),
+            record.status.to_string(),
+        ];
+        write_record(writer, &fields, options)?;
+    }
+    Ok(())
+}
+
+/// Write the observations of the given statements as JSON Lines,
+/// one JSON object per line with the same fields as the long CSV.
+pub fn write_json_lines<W: Write>(
+    writer: &mut W,
+    obs: &ObservationMap,
+    statements: &[ConceptType],
+) -> io::Result<()> {
+    for record in long_records(obs, statements) {
+        serde_json::to_writer(&mut *writer, &record)?;
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{BalanceSheet, Concept};
+
+    #[test]
+    fn write_long_test() {
+        let mut capital = Concept::new("Capital", "C");
+        capital.insert_concept("Capital/Surplus", "C1");
+        capital.update_concept_observation("Capital/Surplus", 9, ObservationStatus::Available);
+        let bs = BalanceSheet::new(
+            Concept::new("Assets", "A"),
+            Concept::new("Liabilities", "L"),
+            capital,
+        );
+        let mut obs = ObservationMap::new();
+        obs.insert(NaiveDate::from_ymd_opt(2020, 5, 27).unwrap(), bs);
+
+        let mut out = Vec::new();
+        write_long_csv(
+            &mut out,
+            &obs,
+            &[ConceptType::Capital],
+            &CsvOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "date,statement,path,series_name,depth,is_leaf,value,status\n\
+             2020-05-27,Capital,Capital,C,0,false,0,missing\n\
+             2020-05-27,Capital,Capital/Surplus,C1,1,true,9,available\n"
+        );
+
+        let mut out = Vec::new();
+        write_json_lines(&mut out, &obs, &[ConceptType::Capital]).unwrap();
+        let lines: Vec<serde_json::Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["path"], "Capital/Surplus");
+        assert_eq!(lines[1]["statement"], "Capital");
+        assert_eq!(lines[1]["status"], "available");
+    }
+}
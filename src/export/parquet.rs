@@ -0,0 +1,207 @@
+use parquet::basic::{LogicalType, Repetition, Type as PhysicalType};
+use parquet::data_type::{BoolType, ByteArray, ByteArrayType, Int32Type, Int64Type};
+use parquet::file::metadata::KeyValue;
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::types::Type;
+use std::io::Write;
+use std::sync::Arc;
+
+use crate::export::columns::{
+    currency, days_since_epoch, leaf_columns, unit, CURRENCY_KEY, UNIT_KEY,
+};
+use crate::export::long::{long_records, LONG_COLUMNS};
+use crate::types::{ConceptType, ObservationMap};
+
+const SCHEMA_NAME: &str = "observations";
+const DATE_COLUMN: &str = "date";
+
+/// Column values of a parquet file, written one column at a time.
+enum Column {
+    Date(Vec<i32>),
+    Int32(Vec<i32>),
+    Int64(Vec<i64>),
+    Bool(Vec<bool>),
+    Text(Vec<ByteArray>),
+}
+
+fn field(name: &str, column: &Column) -> Result<Arc<Type>, Box<dyn std::error::Error>> {
+    let (physical_type, logical_type) = match column {
+        Column::Date(_) => (PhysicalType::INT32, Some(LogicalType::Date)),
+        Column::Int32(_) => (PhysicalType::INT32, None),
+        Column::Int64(_) => (PhysicalType::INT64, None),
+        Column::Bool(_) => (PhysicalType::BOOLEAN, None),
+        Column::Text(_) => (PhysicalType::BYTE_ARRAY, Some(LogicalType::String)),
+    };
+    Ok(Arc::new(
+        Type::primitive_type_builder(name, physical_type)
+            .with_repetition(Repetition::REQUIRED)
+            .with_logical_type(logical_type)
+            .build()?,
+    ))
+}
+
+fn unit_metadata(currency: &str) -> Vec<KeyValue> {
+    vec![
+        KeyValue::new(UNIT_KEY.to_string(), unit(currency)),
+        KeyValue::new(CURRENCY_KEY.to_string(), currency.to_string()),
+    ]
+}
+
+fn write_columns<W: Write + Send>(
+    writer: W,
+    columns: Vec<(String, Column)>,
+    currency: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let fields = columns
+        .iter()
+        .map(|(name, column)| field(name, column))
+        .collect::<Result<Vec<_>, _>>()?;
+    let schema = Type::group_type_builder(SCHEMA_NAME)
+        .with_fields(fields)
+        .build()?;
+    let properties = WriterProperties::builder()
+        .set_key_value_metadata(Some(unit_metadata(currency)))
+        .build();
+
+    let mut file_writer =
+        SerializedFileWriter::new(writer, Arc::new(schema), Arc::new(properties))?;
+    let mut row_group = file_writer.next_row_group()?;
+    for (_, column) in &columns {
+        let mut column_writer = row_group
+            .next_column()?
+            .ok_or("parquet schema with less columns than values")?;
+        match column {
+            Column::Date(values) | Column::Int32(values) => {
+                column_writer
+                    .typed::<Int32Type>()
+                    .write_batch(values, None, None)?;
+            }
+            Column::Int64(values) => {
+                column_writer
+                    .typed::<Int64Type>()
+                    .write_batch(values, None, None)?;
+            }
+            Column::Bool(values) => {
+                column_writer
+                    .typed::<BoolType>()
+                    .write_batch(values, None, None)?;
+            }
+            Column::Text(values) => {
+                column_writer
+                    .typed::<ByteArrayType>()
+                    .write_batch(values, None, None)?;
+            }
+        }
+        column_writer.close()?;
+    }
+    row_group.close()?;
+    file_writer.close()?;
+    Ok(())
+}
+
+/// Write the observations of a statement as a parquet file in wide format:
+/// a DATE column and one INT64 column per leaf concept named by its path.
+///
+/// Values are stored in millions, the unit and currency are written
+/// as key value metadata of the file.
+pub fn write_parquet<W: Write + Send>(
+    writer: W,
+    obs: &ObservationMap,
+    ctype: &ConceptType,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let dates = obs.keys().map(days_since_epoch).collect();
+    let mut columns = vec![(DATE_COLUMN.to_string(), Column::Date(dates))];
+    columns.extend(
+        leaf_columns(obs, ctype)
+            .into_iter()
+            .map(|(c, values)| (c.path.clone(), Column::Int64(values))),
+    );
+    write_columns(writer, columns, currency(obs, &[*ctype]))
+}
+
+/// Write the observations of the given statements as a parquet file in
+/// long format, with the same columns as the long CSV export.
+pub fn write_long_parquet<W: Write + Send>(
+    writer: W,
+    obs: &ObservationMap,
+    statements: &[ConceptType],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut date = Vec::new();
+    let mut statement = Vec::new();
+    let mut path = Vec::new();
+    let mut series_name = Vec::new();
+    let mut depth = Vec::new();
+    let mut is_leaf = Vec::new();
+    let mut value = Vec::new();
+    let mut status = Vec::new();
+    for record in long_records(obs, statements) {
+        date.push(days_since_epoch(&record.date));
+        statement.push(ByteArray::from(record.statement.to_string().as_str()));
+        path.push(ByteArray::from(record.path));
+        series_name.push(ByteArray::from(record.series_name));
+        depth.push(record.depth as i32);
+        is_leaf.push(record.is_leaf);
+        value.push(record.value);
+        status.push(ByteArray::from(record.status.to_string().as_str()));
+    }
+
+    let values = [
+        Column::Date(date),
+        Column::Text(statement),
+        Column::Text(path),
+        Column::Text(series_name),
+        Column::Int32(depth),
+        Column::Bool(is_leaf),
+        Column::Int64(value),
+        Column::Text(status),
+    ];
+    let columns = LONG_COLUMNS
+        .iter()
+        .map(|name| name.to_string())
+        .zip(values)
+        .collect();
+    write_columns(writer, columns, currency(obs, statements))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fed::parse_h41_data;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::RowAccessor;
+    use std::fs::{self, File};
+
+    #[test]
+    fn write_parquet_test() {
+        let text = fs::read_to_string("tests/data/FRB_H41_2010.xml").unwrap();
+        let obs = parse_h41_data(&text).unwrap();
+        let dir =
+            std::env::temp_dir().join(format!("mulligan_parquet_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let wide = dir.join("capital.parquet");
+        write_parquet(File::create(&wide).unwrap(), &obs, &ConceptType::Capital).unwrap();
+        let reader = SerializedFileReader::new(File::open(&wide).unwrap()).unwrap();
+        let metadata = reader.metadata().file_metadata();
+        assert_eq!(metadata.num_rows(), obs.len() as i64);
+        let schema = metadata.schema_descr();
+        assert_eq!(schema.column(0).logical_type(), Some(LogicalType::Date));
+        assert_eq!(schema.column(1).name(), "Capital/Capital paid in");
+        assert_eq!(schema.column(1).physical_type(), PhysicalType::INT64);
+        let unit = metadata.key_value_metadata().unwrap();
+        assert_eq!(unit[0].value.as_deref(), Some("millions of USD"));
+        let first = reader.get_row_iter(None).unwrap().next().unwrap().unwrap();
+        assert_eq!(first.get_long(1).unwrap(), 25651);
+
+        let long = dir.join("observations_long.parquet");
+        let statements = [ConceptType::Assets, ConceptType::Capital];
+        write_long_parquet(File::create(&long).unwrap(), &obs, &statements).unwrap();
+        let reader = SerializedFileReader::new(File::open(&long).unwrap()).unwrap();
+        assert_eq!(
+            reader.metadata().file_metadata().num_rows(),
+            long_records(&obs, &statements).count() as i64
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,172 @@
+use rust_xlsxwriter::{Format, Workbook, Worksheet, XlsxError};
+use std::io::{Seek, Write};
+
+use crate::export::columns::{currency, unit};
+use crate::types::{ConceptType, ObservationMap};
+
+const METADATA_SHEET: &str = "Metadata";
+const DATE_NUM_FORMAT: &str = "yyyy-mm-dd";
+const VALUE_NUM_FORMAT: &str = "#,##0";
+const LABEL_COLUMN_WIDTH: f64 = 60.0;
+const VALUE_COLUMN_WIDTH: f64 = 12.0;
+const METADATA_COLUMNS: [&str; 5] = ["statement", "path", "series_name", "currency", "unit"];
+
+/// Information about the release written in the metadata sheet.
+#[derive(Clone, Debug, Default)]
+pub struct WorkbookInfo {
+    /// identifier of the data source.
+    pub source: String,
+    /// description of the data source.
+    pub description: String,
+    /// URL the data was downloaded from.
+    pub url: String,
+    /// time the data file was prepared by the publisher, if known.
+    pub prepared: Option<String>,
+}
+
+fn write_statement(
+    sheet: &mut Worksheet,
+    obs: &ObservationMap,
+    ctype: &ConceptType,
+) -> Result<(), XlsxError> {
+    let header = Format::new().set_bold();
+    let date_header = Format::new().set_bold().set_num_format(DATE_NUM_FORMAT);
+    let value = Format::new().set_num_format(VALUE_NUM_FORMAT);
+    let total = Format::new().set_bold().set_num_format(VALUE_NUM_FORMAT);
+
+    sheet.set_name(ctype.to_string())?;
+    sheet.set_column_width(0, LABEL_COLUMN_WIDTH)?;
+    sheet.write_string_with_format(0, 0, unit(currency(obs, &[*ctype])), &header)?;
+    for (col, date) in (1..).zip(obs.keys()) {
+        sheet.set_column_width(col, VALUE_COLUMN_WIDTH)?;
+        sheet.write_date_with_format(0, col, date, &date_header)?;
+    }
+
+    let bs = match obs.values().next() {
+        Some(bs) => bs,
+        None => return Ok(()),
+    };
+    for (row, c) in (1..).zip(bs.get_concept(ctype).iter()) {
+        let mut label = Format::new().set_indent(c.depth().min(u8::MAX as usize) as u8);
+        if !c.is_leaf() {
+            label = label.set_bold();
+        }
+        sheet.write_string_with_format(row, 0, c.name(), &label)?;
+    }
+    for (col, bs) in (1..).zip(obs.values()) {
+        for (row, c) in (1..).zip(bs.get_concept(ctype).iter()) {
+            let format = if c.is_leaf() { &value } else { &total };
+            sheet.write_number_with_format(row, col, c.value as f64, format)?;
+        }
+    }
+    sheet.set_freeze_panes(1, 1)?;
+    Ok(())
+}
+
+fn write_metadata(
+    sheet: &mut Worksheet,
+    obs: &ObservationMap,
+    statements: &[ConceptType],
+    info: &WorkbookInfo,
+) -> Result<(), XlsxError> {
+    let bold = Format::new().set_bold();
+    sheet.set_name(METADATA_SHEET)?;
+
+    let release = [
+        ("source", info.source.as_str()),
+        ("description", info.description.as_str()),
+        ("url", info.url.as_str()),
+        ("prepared", info.prepared.as_deref().unwrap_or_default()),
+        ("mulligan_version", env!("CARGO_PKG_VERSION")),
+    ];
+    for (row, (key, value)) in (0..).zip(release) {
+        sheet.write_string_with_format(row, 0, key, &bold)?;
+        sheet.write_string(row, 1, value)?;
+    }
+
+    let first_row = release.len() as u32 + 1;
+    for (col, name) in (0..).zip(METADATA_COLUMNS) {
+        sheet.write_string_with_format(first_row, col, name, &bold)?;
+    }
+    if let Some(bs) = obs.values().next() {
+        let concepts = statements
+            .iter()
+            .flat_map(|ctype| bs.get_concept(ctype).iter().map(move |c| (ctype, c)));
+        for (row, (ctype, c)) in (first_row + 1..).zip(concepts) {
+            sheet.write_string(row, 0, ctype.to_string())?;
+            sheet.write_string(row, 1, &c.path)?;
+            sheet.write_string(row, 2, &c.series_name)?;
+            sheet.write_string(row, 3, &c.currency)?;
+            sheet.write_string(row, 4, unit(&c.currency))?;
+        }
+    }
+    sheet.autofit();
+    Ok(())
+}
+
+/// Build a workbook with a sheet per statement, holding a row per concept
+/// labelled by its name indented by depth and a column per date, followed
+/// by a metadata sheet with the series of every concept and the release info.
+///
+/// Values are written in millions, as published.
+pub fn workbook(
+    obs: &ObservationMap,
+    statements: &[ConceptType],
+    info: &WorkbookInfo,
+) -> Result<Workbook, XlsxError> {
+    let mut workbook = Workbook::new();
+    for ctype in statements {
+        write_statement(workbook.add_worksheet(), obs, ctype)?;
+    }
+    write_metadata(workbook.add_worksheet(), obs, statements, info)?;
+    Ok(workbook)
+}
+
+/// Write the observations as an xlsx workbook, see [`workbook`].
+pub fn write_xlsx<W: Write + Seek + Send>(
+    writer: W,
+    obs: &ObservationMap,
+    statements: &[ConceptType],
+    info: &WorkbookInfo,
+) -> Result<(), XlsxError> {
+    workbook(obs, statements, info)?.save_to_writer(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fed::parse_h41_data;
+    use std::fs;
+    use std::io::{Cursor, Read};
+    use zip::ZipArchive;
+
+    #[test]
+    fn write_xlsx_test() {
+        let text = fs::read_to_string("tests/data/FRB_H41_2010.xml").unwrap();
+        let obs = parse_h41_data(&text).unwrap();
+        let info = WorkbookInfo {
+            source: "h41".to_string(),
+            ..WorkbookInfo::default()
+        };
+
+        let mut out = Cursor::new(Vec::new());
+        write_xlsx(&mut out, &obs, &[ConceptType::Capital], &info).unwrap();
+        let mut archive = ZipArchive::new(out).unwrap();
+        let mut workbook_xml = String::new();
+        archive
+            .by_name("xl/workbook.xml")
+            .unwrap()
+            .read_to_string(&mut workbook_xml)
+            .unwrap();
+        assert!(workbook_xml.contains("name=\"Capital\""));
+        assert!(workbook_xml.contains("name=\"Metadata\""));
+        let mut strings = String::new();
+        archive
+            .by_name("xl/sharedStrings.xml")
+            .unwrap()
+            .read_to_string(&mut strings)
+            .unwrap();
+        assert!(strings.contains("millions of USD"));
+        assert!(strings.contains("RESPPLCP_N.WW"));
+    }
+}
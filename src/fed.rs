@@ -1,10 +1,18 @@
 use chrono::NaiveDate;
 use radix_trie::{Trie, TrieCommon};
 use roxmltree::{Document, Node};
-use std::collections::{BTreeMap, HashMap, HashSet};
-
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::columnar::{ConceptSchema, ObservationStore};
+use crate::download::{self, FetchOptions, HttpClient};
+use crate::residual;
+use crate::source::DataSource;
 use crate::types;
-use crate::types::{BalanceSheet, Concept, ConceptType};
+pub use crate::types::ObservationMap;
+use crate::types::{BalanceSheet, Concept, ConceptType, ObservationStatus};
+use crate::validate::Tolerance;
 
 /*
     Urls for FED H.4.1 statistical data
@@ -13,10 +21,17 @@ use crate::types::{BalanceSheet, Concept, ConceptType};
     https://www.federalreserve.gov/releases/H41/20200507/
     https://www.federalreserve.gov/releases/H41/20200507/H41.TXT
 */
-/// URL to download zip file containing FED H.4.1 statistical information.
+/// URL to download zip file containing FED H.4.1 statistical information,
+/// i.e. [`H41_FED_DOWNLOAD_PATH`] under [`H41_FED_BASE_URL`].
 pub const H41_FED_URL: &str =
     "https://www.federalreserve.gov/datadownload/Output.aspx?rel=H41&filetype=zip";
 
+/// Base URL of the federal reserve website.
+pub const H41_FED_BASE_URL: &str = "https://www.federalreserve.gov";
+
+/// Path, relative to [`H41_FED_BASE_URL`], of the H.4.1 zip file.
+pub const H41_FED_DOWNLOAD_PATH: &str = "/datadownload/Output.aspx?rel=H41&filetype=zip";
+
 /// File name that contains the XML structure of H.4.1 data.
 pub const H41_STRUCT_XML: &str = "H41_struct.xml";
 
@@ -26,11 +41,13 @@ pub const H41_DATA_XML: &str = "H41_data.xml";
 pub(crate) const FED_ASSETS_SERIES_NAME: &str = "RESPPA_N.WW";
 pub(crate) const FED_LIABILITIES_SERIES_NAME: &str = "RESPPLL_N.WW";
 pub(crate) const FED_CAPITAL_SERIES_NAME: &str = "RESPPLC_N.WW";
+pub(crate) const FED_CURRENCY: &str = "USD";
 
 const STRUCTURE_NS: &str = "http://www.SDMX.org/resources/SDMXML/schemas/v1_0/structure";
 const KF_NS: &str = "http://www.federalreserve.gov/structure/compact/H41_H41";
 const FRB_NS: &str = "http://www.federalreserve.gov/structure/compact/common";
 const COMMON_NS: &str = "http://www.SDMX.org/resources/SDMXML/schemas/v1_0/common";
+const MESSAGE_NS: &str = "http://www.SDMX.org/resources/SDMXML/schemas/v1_0/message";
 
 /*
     FED ASSETS Series discarded for assets in balance sheet:
@@ -77,11 +94,13 @@ const COMMON_NS: &str = "http://www.SDMX.org/resources/SDMXML/schemas/v1_0/commo
     RESH4SCF_N.WW -> subcategory(OFSRB): Other Factors Supplying Reserve Balances -> component(FLT): Float
     RESH4SO_N.WW -> subcategory(OFSRB): Other Factors Supplying Reserve Balances -> component(OFRA): Other Federal Reserve assets
     RESTBMT_N.WW -> subcategory(OFSRB): Other Factors Supplying Reserve Balances -> component(TCO): Treasury currency outstanding
-    RESPPALSD_N.WW -> subcategory(OFSRB):  Unamortized discounts on securities held outright -> component(DISCSHOR)
-    RESPPALSP_N.WW ->  subcategory(OFSRB):  Unamortized premiums on securities held outright -> component(PREMSHOR)
-    RESPPAOF_N.WW -> subcategory(OFSRB): Other Factors Supplying Reserve Balances: -> compoent(FCDA):  Foreign currency denominated assets
     RESTBMT_N.WW -> subcategory(OFSRB): Other Factors Supplying Reserve Balances -> component(TCO):Treasury currency outstanding
 
+    The following subcategory(OFSRB) series are kept as they are part of total assets:
+    RESPPALSD_N.WW -> Unamortized discounts on securities held outright -> component(DISCSHOR)
+    RESPPALSP_N.WW -> Unamortized premiums on securities held outright -> component(PREMSHOR)
+    RESPPAOF_N.WW -> Other Factors Supplying Reserve Balances: -> compoent(FCDA):  Foreign currency denominated assets
+
     ************************************************************************************
 
     LIABILITIES SERIES DISCARDED:
@@ -96,6 +115,12 @@ const COMMON_NS: &str = "http://www.SDMX.org/resources/SDMXML/schemas/v1_0/commo
 
     "RESPPLLOO_N.WW" redundant because of "RESPPLLO_N.WW"
 
+    "RESPPLLOP_N.WW" earnings remittances are included in "RESPPLLO_N.WW"
+
+    LIABILITIES SERIES INCLUDED FROM OTHER FACTORS DRAINING RESERVE BALANCES (OFDRB):
+
+    "RESH4E_N.WW" treasury contribution to credit facilities, part of total liabilities
+
     *************************************************************************************************
 
     CAPITAL SERIES DISCARDED
@@ -124,9 +149,6 @@ lazy_static! {
         m.insert("RESH4SCF_N.WW");
         m.insert("RESH4SO_N.WW");
         m.insert("RESTBMT_N.WW");
-        m.insert("RESPPALSD_N.WW");
-        m.insert("RESPPALSP_N.WW");
-        m.insert("RESPPAOF_N.WW");
         m.insert("RESTBMT_N.WW");
         m.insert("RESPPLLDE_N.WW");
         m.insert("RESPPLLDO_N.WW");
@@ -134,19 +156,24 @@ lazy_static! {
         m.insert("RESPPLLNH_N.WW");
         m.insert("RESPPLLNO_N.WW");
         m.insert("RESPPLLOO_N.WW");
+        m.insert("RESPPLLOP_N.WW");
+        m
+    };
+    static ref SERIES_TO_INCLUDE: HashSet<&'static str> = {
+        let mut m = HashSet::new();
+        m.insert("RESH4E_N.WW");
         m
     };
 }
 
+const HEADER_TAG: &str = "Header";
+const PREPARED_TAG: &str = "Prepared";
 const CODE_LIST_TAG: &str = "CodeList";
 const CODE_TAG: &str = "Code";
 const SERIES_TAG: &str = "Series";
 const OBS_TAG: &str = "Obs";
 const ANNOTATION_TEXT_TAG: &str = "AnnotationText";
 
-/// Ordered map containing balance sheet grouped for each date.
-pub type ObservationMap = BTreeMap<NaiveDate, BalanceSheet>;
-
 type ConceptMap = HashMap<String, HashMap<String, String>>;
 
 fn get_asset_series<'a>(doc: &'a Document<'_>) -> Vec<Node<'a, 'a>> {
@@ -170,7 +197,8 @@ fn get_liabilities_series<'a>(doc: &'a Document<'_>) -> Vec<Node<'a, 'a>> {
                 && n.has_tag_name((KF_NS, SERIES_TAG))
                 && n.attribute("CATEGORY") == Some("LIABCAP")
                 && n.attribute("SUBCATEGORY") != Some("CAP")
-                && n.attribute("SUBCATEGORY") != Some("OFDRB")
+                && (n.attribute("SUBCATEGORY") != Some("OFDRB")
+                    || SERIES_TO_INCLUDE.contains(n.attribute("SERIES_NAME").unwrap()))
                 && n.attribute("SUBCATEGORY") != Some("TLC")
                 && n.attribute("DISTRIBUTION") == Some("TOT")
                 && n.attribute("SERIESTYPE") == Some("L")
@@ -230,6 +258,10 @@ fn parse_asset_annotation(annotation: &str) -> String {
         ": Securities Held Outright: Securities held outright",
         ": Securities Held Outright",
     );
+    let path = path.replace(
+        ": Other Factors Supplying Reserve Balances: Foreign currency denominated assets",
+        ": Foreign currency denominated assets",
+    );
     let path = path.replace(": All", "");
     let path = path.replace("Discontinued: ", "");
     let path = path.replace(": ", "/");
@@ -250,6 +282,10 @@ fn parse_liability_annotation(annotation: &str) -> String {
         ": Deposits with F.R. Banks, other than reserve balances",
         ": Deposits",
     );
+    let path = path.replace(
+        "Other Factors Draining Reserve Balances: Treasury Contribution",
+        "Liabilities: Treasury Contribution",
+    );
     let path = path.replace("Liabilities and Capital: ", "");
     let path = path.replace(": All", "");
     let path = path.replace("Discontinued: ", "");
@@ -278,6 +314,7 @@ fn paths_to_balance_sheet_assets(
     capital_paths: Trie<String, String>,
 ) -> BalanceSheet {
     let mut assets = Concept::new(types::ASSETS_PATH, FED_ASSETS_SERIES_NAME);
+    assets.currency = FED_CURRENCY.to_string();
 
     for (path, series_name) in assets_paths.iter() {
         if path == types::ASSETS_PATH {
@@ -287,6 +324,7 @@ fn paths_to_balance_sheet_assets(
     }
 
     let mut liabilities = Concept::new(types::LIABILITIES_PATH, FED_LIABILITIES_SERIES_NAME);
+    liabilities.currency = FED_CURRENCY.to_string();
 
     for (path, series_name) in liabilities_paths.iter() {
         if path == types::LIABILITIES_PATH {
@@ -296,6 +334,7 @@ fn paths_to_balance_sheet_assets(
     }
 
     let mut capital = Concept::new(types::CAPITAL_PATH, FED_CAPITAL_SERIES_NAME);
+    capital.currency = FED_CURRENCY.to_string();
 
     for (path, series_name) in capital_paths.iter() {
         if path == types::CAPITAL_PATH {
@@ -348,42 +387,95 @@ fn get_paths(
     Ok(paths)
 }
 
-fn fill_observations(
-    obs: &mut ObservationMap,
-    bs_template: &BalanceSheet,
+type SeriesObservation = (NaiveDate, i64, ObservationStatus);
+
+fn series_observations(
+    serie: &Node<'_, '_>,
+) -> Result<Vec<SeriesObservation>, Box<dyn std::error::Error>> {
+    let mut observations = Vec::new();
+    for observation in get_children_node_elements(serie, FRB_NS, OBS_TAG) {
+        let date =
+            NaiveDate::parse_from_str(observation.attribute("TIME_PERIOD").unwrap(), "%Y-%m-%d")?;
+        let (value, status) = if observation.attribute("OBS_STATUS") == Some("A") {
+            (
+                observation.attribute("OBS_VALUE").unwrap(),
+                ObservationStatus::Available,
+            )
+        } else {
+            ("0", ObservationStatus::NotAvailable)
+        };
+        observations.push((date, value.parse::<i64>().unwrap_or(0), status));
+    }
+    Ok(observations)
+}
+
+// Observations of every series along with the index of their concept in
+// the schema.
+fn concept_observations(
+    schema: &ConceptSchema,
     series: &mut Vec<Node<'_, '_>>,
     parse_fn: fn(&str) -> String,
-    ctype: &ConceptType,
+    observations: &mut Vec<(usize, Vec<SeriesObservation>)>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     for serie in series {
         let annotation = get_annotation(serie);
         let path = parse_fn(&annotation);
-        let mut observations = get_children_node_elements(serie, FRB_NS, OBS_TAG);
-        for observation in &mut observations {
-            let date = NaiveDate::parse_from_str(
-                observation.attribute("TIME_PERIOD").unwrap(),
-                "%Y-%m-%d",
-            )?;
-            let value = if observation.attribute("OBS_STATUS") == Some("A") {
-                observation.attribute("OBS_VALUE").unwrap()
-            } else {
-                "0"
-            };
-            obs.entry(date)
-                .or_insert_with(|| bs_template.clone())
-                .get_concept_mut(ctype)
-                .update_concept_value(&path, value.parse::<i64>().unwrap_or(0));
-        }
+        let concept = schema
+            .position(&path)
+            .ok_or_else(|| format!("series of unknown concept {path}"))?;
+        observations.push((concept, series_observations(serie)?));
     }
 
     Ok(())
 }
 
+/// Options controlling how H.4.1 data files are parsed.
+#[derive(Clone, Debug, Default)]
+pub struct ParseOptions {
+    /// roll up intermediate concepts without series from their children,
+    /// see [`BalanceSheet::rollup`].
+    pub rollup: bool,
+    /// add residual leaves under published totals exceeding their children
+    /// beyond the default tolerance, see [`residual::add_residuals`].
+    pub residuals: bool,
+}
+
 /// Parse H.4.1 fed XML data file to return an ordered map with a
 /// balance sheet for each period of time.
 pub fn parse_h41_data(text: &str) -> Result<ObservationMap, Box<dyn std::error::Error>> {
+    parse_h41_data_with_options(text, &ParseOptions::default())
+}
+
+/// Parse H.4.1 fed XML data file as [`parse_h41_data`] does,
+/// according to `options`.
+///
+/// The data is parsed into an [`ObservationStore`] first, see
+/// [`parse_h41_store`], so every balance sheet shares its concept tree
+/// index.
+pub fn parse_h41_data_with_options(
+    text: &str,
+    options: &ParseOptions,
+) -> Result<ObservationMap, Box<dyn std::error::Error>> {
+    let mut obs = parse_h41_store(text)?.to_observations();
+    apply_options(&mut obs, options);
+    Ok(obs)
+}
+
+fn apply_options(obs: &mut ObservationMap, options: &ParseOptions) {
+    if options.residuals {
+        residual::add_residuals(obs, &Tolerance::default());
+    }
+    if options.rollup {
+        for bs in obs.values_mut() {
+            bs.rollup();
+        }
+    }
+}
+
+/// Parse H.4.1 fed XML data file into a columnar store, sharing
+/// a single copy of the concept tree between every date.
+pub fn parse_h41_store(text: &str) -> Result<ObservationStore, Box<dyn std::error::Error>> {
     let doc = Document::parse(text)?;
-    let mut obs: ObservationMap = BTreeMap::new();
 
     let mut asset_series: Vec<Node<'_, '_>> = get_asset_series(&doc);
     let mut liabilities_series: Vec<Node<'_, '_>> = get_liabilities_series(&doc);
@@ -394,30 +486,139 @@ pub fn parse_h41_data(text: &str) -> Result<ObservationMap, Box<dyn std::error::
         get_paths(&mut liabilities_series, parse_liability_annotation)?,
         get_paths(&mut capital_series, parse_capital_annotation)?,
     );
+    let schema = ConceptSchema::from_balance_sheet(&bs_template);
 
-    fill_observations(
-        &mut obs,
-        &bs_template,
+    let mut observations = Vec::new();
+    concept_observations(
+        &schema,
         &mut asset_series,
         parse_asset_annotation,
-        &ConceptType::Assets,
+        &mut observations,
     )?;
-    fill_observations(
-        &mut obs,
-        &bs_template,
+    concept_observations(
+        &schema,
         &mut liabilities_series,
         parse_liability_annotation,
-        &ConceptType::Liabilities,
+        &mut observations,
     )?;
-    fill_observations(
-        &mut obs,
-        &bs_template,
+    concept_observations(
+        &schema,
         &mut capital_series,
         parse_capital_annotation,
-        &ConceptType::Capital,
+        &mut observations,
     )?;
 
-    Ok(obs)
+    let dates = observations
+        .iter()
+        .flat_map(|(_, obs)| obs.iter().map(|(date, _, _)| *date))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let mut store = ObservationStore::with_dates(Arc::new(schema), dates);
+    let index: HashMap<NaiveDate, usize> = store
+        .dates()
+        .iter()
+        .enumerate()
+        .map(|(i, date)| (*date, i))
+        .collect();
+    for (concept, obs) in observations {
+        for (date, value, status) in obs {
+            store.set_at(index[&date], concept, value, status);
+        }
+    }
+
+    Ok(store)
+}
+
+/// Parse H.4.1 fed XML data file into a columnar store as
+/// [`parse_h41_store`] does, according to `options`.
+pub fn parse_h41_store_with_options(
+    text: &str,
+    options: &ParseOptions,
+) -> Result<ObservationStore, Box<dyn std::error::Error>> {
+    let store = parse_h41_store(text)?;
+    if !options.residuals && !options.rollup {
+        return Ok(store);
+    }
+    let mut obs = store.to_observations();
+    apply_options(&mut obs, options);
+    ObservationStore::from_observations(&obs)
+}
+
+/// Return the time the H.4.1 XML data file was prepared, as stated in its
+/// header, `None` if the header does not state it.
+pub fn parse_h41_prepared(text: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let doc = Document::parse(text)?;
+    let prepared = doc
+        .root_element()
+        .children()
+        .find(|n| n.has_tag_name((MESSAGE_NS, HEADER_TAG)))
+        .and_then(|header| {
+            header
+                .children()
+                .find(|n| n.has_tag_name((MESSAGE_NS, PREPARED_TAG)))
+        })
+        .and_then(|prepared| prepared.text())
+        .map(str::trim)
+        .filter(|prepared| !prepared.is_empty())
+        .map(str::to_string);
+    Ok(prepared)
+}
+
+/// Download the H.4.1 zip file into memory.
+pub fn fetch_h41(
+    client: &dyn HttpClient,
+    options: &FetchOptions,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let url = options.url(H41_FED_BASE_URL, H41_FED_DOWNLOAD_PATH);
+    Ok(download::fetch(client, &url, &[], options)?.body)
+}
+
+/// Download the H.4.1 zip file to the given path.
+pub fn fetch_h41_to_path(
+    client: &dyn HttpClient,
+    options: &FetchOptions,
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let bytes = fetch_h41(client, options)?;
+    download::write_file(path, &bytes)
+}
+
+/// H.4.1 factors affecting reserve balances release.
+pub struct H41Source;
+
+impl DataSource for H41Source {
+    fn id(&self) -> &'static str {
+        "h41"
+    }
+
+    fn description(&self) -> &'static str {
+        "Federal reserve H.4.1 factors affecting reserve balances"
+    }
+
+    fn urls(&self) -> &'static [&'static str] {
+        &[H41_FED_URL]
+    }
+
+    fn archive_members(&self) -> &'static [&'static str] {
+        &[H41_STRUCT_XML, H41_DATA_XML]
+    }
+
+    fn data_file(&self) -> &'static str {
+        H41_DATA_XML
+    }
+
+    fn statements(&self) -> &'static [ConceptType] {
+        &ConceptType::ALL
+    }
+
+    fn prepared(&self, text: &str) -> Option<String> {
+        parse_h41_prepared(text).ok().flatten()
+    }
+
+    fn parse(&self, text: &str) -> Result<ObservationMap, Box<dyn std::error::Error>> {
+        parse_h41_data(text)
+    }
 }
 
 #[cfg(test)]
@@ -433,4 +634,43 @@ mod tests {
         assert_eq!("Assets/Liquidity and Credit Facilities/Net portfolio holdings of Commercial Paper Funding Facility LLC",
             parse_asset_annotation("Discontinued: Assets: Liquidity and Credit Facilities: Net portfolio holdings of Commercial Paper Funding Facility LLC: Wednesday level"));
     }
+
+    #[test]
+    fn h41_fed_url_test() {
+        assert_eq!(
+            H41_FED_URL,
+            format!("{H41_FED_BASE_URL}{H41_FED_DOWNLOAD_PATH}")
+        );
+    }
+
+    #[test]
+    fn parse_h41_prepared_test() {
+        let text = std::fs::read_to_string("tests/data/FRB_H41_2020.xml").unwrap();
+        assert_eq!(
+            Some("2020-05-28T14:27:09Z".to_string()),
+            parse_h41_prepared(&text).unwrap()
+        );
+        let message = |header: &str| {
+            format!(
+                "<m:MessageGroup xmlns:m=\"{MESSAGE_NS}\">{header}\
+                 <m:Prepared>x</m:Prepared></m:MessageGroup>"
+            )
+        };
+        assert_eq!(
+            None,
+            parse_h41_prepared(&message("<m:Header><m:ID>H41</m:ID></m:Header>")).unwrap()
+        );
+        assert_eq!(
+            None,
+            parse_h41_prepared(&message("<m:Header><m:Prepared/></m:Header>")).unwrap()
+        );
+        assert_eq!(
+            Some("2020-05-28T14:27:09Z".to_string()),
+            parse_h41_prepared(&message(
+                "<m:Header>\n<m:Prepared> 2020-05-28T14:27:09Z </m:Prepared></m:Header>"
+            ))
+            .unwrap()
+        );
+        assert!(parse_h41_prepared("<Header></Prepared>").is_err());
+    }
 }
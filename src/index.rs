@@ -0,0 +1,44 @@
+use crate::types::{Concept, UNDEFINED_SERIES_NAME};
+use std::collections::HashMap;
+
+/// Index of a concept tree, locating every concept by the positions of the
+/// children to follow from the root.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ConceptIndex {
+    paths: HashMap<String, Vec<usize>>,
+    series: HashMap<String, Vec<usize>>,
+}
+
+impl ConceptIndex {
+    pub(crate) fn new(root: &Concept) -> ConceptIndex {
+        let mut index = ConceptIndex::default();
+        index.add(root, &mut Vec::new());
+        index
+    }
+
+    fn add(&mut self, concept: &Concept, route: &mut Vec<usize>) {
+        self.paths
+            .entry(concept.path.clone())
+            .or_insert_with(|| route.clone());
+        if concept.series_name != UNDEFINED_SERIES_NAME {
+            self.series
+                .entry(concept.series_name.clone())
+                .or_insert_with(|| route.clone());
+        }
+        for (i, child) in concept.children.iter().enumerate() {
+            route.push(i);
+            self.add(child, route);
+            route.pop();
+        }
+    }
+
+    /// Route to the concept with the given path.
+    pub(crate) fn path(&self, path: &str) -> Option<&[usize]> {
+        self.paths.get(path).map(Vec::as_slice)
+    }
+
+    /// Route to the first concept extracted from the given series.
+    pub(crate) fn series(&self, series_name: &str) -> Option<&[usize]> {
+        self.series.get(series_name).map(Vec::as_slice)
+    }
+}
@@ -1,7 +1,8 @@
 use crate::types::Concept;
+use std::collections::VecDeque;
 use std::slice;
 
-/// Iterator over concepts.
+/// Pre-order iterator over concepts, see [`Concept::iter`].
 pub struct Iter<'a> {
     root: &'a Concept,
     root_visited: bool,
@@ -9,8 +10,7 @@ pub struct Iter<'a> {
 }
 
 impl<'a> Iter<'a> {
-    // TODO: make this private somehow (and same for the other iterators).
-    pub fn new(root: &'a Concept) -> Iter<'a> {
+    pub(crate) fn new(root: &'a Concept) -> Iter<'a> {
         Iter {
             root,
             root_visited: false,
@@ -59,6 +59,129 @@ impl<'a> Iterator for Iter<'a> {
     }
 }
 
+/// Iterator over the leaf concepts, see [`Concept::leaves`].
+pub struct Leaves<'a> {
+    iter: Iter<'a>,
+}
+
+impl<'a> Leaves<'a> {
+    pub(crate) fn new(root: &'a Concept) -> Leaves<'a> {
+        Leaves {
+            iter: Iter::new(root),
+        }
+    }
+}
+
+impl<'a> Iterator for Leaves<'a> {
+    type Item = &'a Concept;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.by_ref().find(|c| c.is_leaf())
+    }
+}
+
+/// Post-order iterator over concepts, see [`Concept::post_order`].
+pub struct PostOrder<'a> {
+    stack: Vec<(&'a Concept, slice::Iter<'a, Concept>)>,
+}
+
+impl<'a> PostOrder<'a> {
+    pub(crate) fn new(root: &'a Concept) -> PostOrder<'a> {
+        PostOrder {
+            stack: vec![(root, root.children.iter())],
+        }
+    }
+}
+
+impl<'a> Iterator for PostOrder<'a> {
+    type Item = &'a Concept;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (_, children) = self.stack.last_mut()?;
+            match children.next() {
+                Some(child) => self.stack.push((child, child.children.iter())),
+                None => return self.stack.pop().map(|(concept, _)| concept),
+            }
+        }
+    }
+}
+
+/// Breadth-first iterator over concepts, see [`Concept::breadth_first`].
+pub struct BreadthFirst<'a> {
+    queue: VecDeque<&'a Concept>,
+}
+
+impl<'a> BreadthFirst<'a> {
+    pub(crate) fn new(root: &'a Concept) -> BreadthFirst<'a> {
+        BreadthFirst {
+            queue: VecDeque::from([root]),
+        }
+    }
+}
+
+impl<'a> Iterator for BreadthFirst<'a> {
+    type Item = &'a Concept;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let concept = self.queue.pop_front()?;
+        self.queue.extend(concept.children.iter());
+        Some(concept)
+    }
+}
+
+/// Pre-order iterator over concepts along with their depth below the
+/// concept iterated and their parent, see [`Concept::iter_with_parents`].
+pub struct WithParents<'a> {
+    root: Option<&'a Concept>,
+    stack: Vec<(&'a Concept, slice::Iter<'a, Concept>)>,
+}
+
+impl<'a> WithParents<'a> {
+    pub(crate) fn new(root: &'a Concept) -> WithParents<'a> {
+        WithParents {
+            root: Some(root),
+            stack: vec![],
+        }
+    }
+}
+
+impl<'a> Iterator for WithParents<'a> {
+    type Item = (usize, Option<&'a Concept>, &'a Concept);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(root) = self.root.take() {
+            self.stack.push((root, root.children.iter()));
+            return Some((0, None, root));
+        }
+
+        loop {
+            let depth = self.stack.len();
+            let (parent, children) = self.stack.last_mut()?;
+            let parent = *parent;
+            match children.next() {
+                Some(child) => {
+                    self.stack.push((child, child.children.iter()));
+                    return Some((depth, Some(parent), child));
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+/// Visitor of a concept tree allowed to update the concepts, see
+/// [`Concept::walk_mut`].
+pub trait VisitorMut {
+    /// Called on a concept before visiting its children.
+    fn enter(&mut self, _concept: &mut Concept) {}
+
+    /// Called on a concept after visiting its children.
+    fn leave(&mut self, _concept: &mut Concept) {}
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -77,4 +200,59 @@ mod tests {
             "Net portfolio holdings of Commercial Paper Funding Facility LLC"
         );
     }
+
+    #[test]
+    fn traversal_test() {
+        let mut assets = Concept::new(types::ASSETS_PATH, fed::FED_ASSETS_SERIES_NAME);
+        assets.insert_concept("Assets/A/A1", "A1");
+        assets.insert_concept("Assets/A/A2", "A2");
+        assets.insert_concept("Assets/B", "B");
+        let names = |it: &mut dyn Iterator<Item = &Concept>| {
+            it.map(|c| c.name().to_string()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(&mut assets.leaves()), ["A1", "A2", "B"]);
+        assert_eq!(
+            names(&mut assets.post_order()),
+            ["A1", "A2", "A", "B", "Assets"]
+        );
+        assert_eq!(
+            names(&mut assets.breadth_first()),
+            ["Assets", "A", "B", "A1", "A2"]
+        );
+        let with_parents: Vec<_> = assets
+            .iter_with_parents()
+            .map(|(depth, parent, c)| (depth, parent.map(|p| p.name()), c.name()))
+            .collect();
+        assert_eq!(
+            with_parents,
+            [
+                (0, None, "Assets"),
+                (1, Some("Assets"), "A"),
+                (2, Some("A"), "A1"),
+                (2, Some("A"), "A2"),
+                (1, Some("Assets"), "B"),
+            ]
+        );
+        assert_eq!(assets.children().len(), 2);
+
+        struct Total;
+        impl VisitorMut for Total {
+            fn enter(&mut self, concept: &mut Concept) {
+                if concept.is_leaf() {
+                    concept.value = 1;
+                }
+            }
+
+            fn leave(&mut self, concept: &mut Concept) {
+                if !concept.is_leaf() {
+                    concept.value = concept.children().iter().map(|c| c.value).sum();
+                }
+            }
+        }
+        assets.walk_mut(&mut Total);
+        assert_eq!(assets.value, 3);
+        assets.for_each_mut(|c| c.value *= 2);
+        assert_eq!(assets.get("Assets/A").unwrap().value, 4);
+    }
 }
@@ -4,14 +4,50 @@
 //!
 //! Current support:
 //! * Extract H.4.1 federal reserve balance sheet information.
+//! * Extract Eurosystem consolidated weekly financial statement information.
 
 #[macro_use]
 extern crate lazy_static;
 
+/// Provides safe extraction of downloaded zip archives.
+pub mod archive;
+/// Provides a download cache based on HTTP conditional requests.
+pub mod cache;
+/// Provides changes between balance sheets over time.
+pub mod change;
+/// Provides a columnar representation of observations sharing the concept tree.
+pub mod columnar;
+/// Provides functionality to download releases through a pluggable HTTP transport.
+pub mod download;
+/// Provides parsing functionality to extract european central bank information.
+pub mod ecb;
+/// Provides exporters of observations to different file formats.
+pub mod export;
 /// Provides parsing functionality to extract federal reserve information.
 pub mod fed;
-mod iter;
+mod index;
+/// Provides iterators and visitors over concept trees.
+pub mod iter;
+/// Provides integrity manifests for downloaded and extracted files.
+pub mod manifest;
+/// Provides resampling of observations to calendar periods.
+pub mod resample;
+/// Provides synthetic residual leaves completing published totals.
+pub mod residual;
+/// Provides selection of dates and concepts from observations.
+pub mod select;
+/// Provides time series of concepts over observation dates.
+pub mod series;
+/// Provides a compact binary snapshot format for observations.
+pub mod snapshot;
+/// Provides a common interface over the supported statistical releases.
+pub mod source;
+/// Provides SQLite storage of observations.
+#[cfg(feature = "sqlite")]
+pub mod store;
 mod types;
+/// Provides accounting consistency checks of balance sheets.
+pub mod validate;
 
-pub use self::types::{BalanceSheet, Concept, ConceptType};
+pub use self::types::{BalanceSheet, Concept, ConceptType, ObservationMap, ObservationStatus};
 pub use chrono::NaiveDate;
@@ -0,0 +1,188 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::cache::{sha256_hex, CachedDownload};
+use crate::source::DataSource;
+
+/// File name of the manifest written inside an output directory.
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// File extracted from a download and recorded in a [`Manifest`].
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ManifestMember {
+    /// file name relative to the output directory.
+    pub name: String,
+    /// file size in bytes.
+    pub size: u64,
+    /// hex encoded SHA-256 of the file.
+    pub sha256: String,
+}
+
+/// Record of what an output directory was built from.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Manifest {
+    /// identifier of the data source.
+    pub source: String,
+    /// URL the data was downloaded from.
+    pub url: String,
+    /// time the data was downloaded.
+    pub downloaded_at: DateTime<Utc>,
+    /// hex encoded SHA-256 of the downloaded file.
+    pub sha256: String,
+    /// files extracted from the download.
+    pub members: Vec<ManifestMember>,
+    /// time the data file was prepared by the publisher, if stated.
+    pub prepared: Option<String>,
+    /// version of mulligan that built the output directory.
+    pub mulligan_version: String,
+}
+
+/// Difference found when verifying an output directory against its manifest.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Mismatch {
+    /// member listed in the manifest but missing from the directory.
+    Missing(String),
+    /// member whose content changed since the manifest was written.
+    Modified {
+        /// member name.
+        name: String,
+        /// hash recorded in the manifest.
+        expected: String,
+        /// hash of the current content.
+        actual: String,
+    },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::Missing(name) => write!(f, "{name}: missing"),
+            Mismatch::Modified {
+                name,
+                expected,
+                actual,
+            } => write!(f, "{name}: expected sha256 {expected}, found {actual}"),
+        }
+    }
+}
+
+impl Manifest {
+    /// Build the manifest of `dir` from the download it was extracted
+    /// from and the names of the extracted members.
+    pub fn new(
+        source: &dyn DataSource,
+        download: &CachedDownload,
+        dir: &Path,
+        members: &[String],
+    ) -> Result<Manifest, Box<dyn std::error::Error>> {
+        let mut manifest_members = Vec::new();
+        let mut prepared = None;
+        for name in members {
+            let bytes = fs::read(dir.join(name))?;
+            if name == source.data_file() {
+                prepared = source.prepared(&String::from_utf8_lossy(&bytes));
+            }
+            manifest_members.push(ManifestMember {
+                name: name.clone(),
+                size: bytes.len() as u64,
+                sha256: sha256_hex(&bytes),
+            });
+        }
+
+        Ok(Manifest {
+            source: source.id().to_string(),
+            url: download.entry.url.clone(),
+            downloaded_at: download.entry.downloaded_at,
+            sha256: download.entry.sha256.clone(),
+            members: manifest_members,
+            prepared,
+            mulligan_version: env!("CARGO_PKG_VERSION").to_string(),
+        })
+    }
+
+    /// Read the manifest of an output directory.
+    pub fn read(dir: &Path) -> Result<Manifest, Box<dyn std::error::Error>> {
+        let text = fs::read_to_string(dir.join(MANIFEST_FILE_NAME))?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Write the manifest inside an output directory.
+    pub fn write(&self, dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(
+            dir.join(MANIFEST_FILE_NAME),
+            serde_json::to_string_pretty(self)?,
+        )?;
+        Ok(())
+    }
+
+    /// Check the members found in `dir` against the manifest.
+    pub fn verify(&self, dir: &Path) -> Vec<Mismatch> {
+        self.members
+            .iter()
+            .filter_map(|member| match fs::read(dir.join(&member.name)) {
+                Err(_) => Some(Mismatch::Missing(member.name.clone())),
+                Ok(bytes) => {
+                    let actual = sha256_hex(&bytes);
+                    (actual != member.sha256).then(|| Mismatch::Modified {
+                        name: member.name.clone(),
+                        expected: member.sha256.clone(),
+                        actual,
+                    })
+                }
+            })
+            .collect()
+    }
+
+    /// Check a downloaded file against the hash recorded in the manifest.
+    pub fn verify_download(&self, path: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+        Ok(sha256_hex(&fs::read(path)?) == self.sha256)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::CacheEntry;
+    use crate::fed::{H41Source, H41_DATA_XML};
+    use std::path::PathBuf;
+
+    #[test]
+    fn manifest_verify_test() {
+        let dir =
+            std::env::temp_dir().join(format!("mulligan_manifest_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::copy("tests/data/FRB_H41_2020.xml", dir.join(H41_DATA_XML)).unwrap();
+        let download = CachedDownload {
+            path: PathBuf::from("h41.download"),
+            entry: CacheEntry {
+                url: "http://h/".to_string(),
+                sha256: sha256_hex(b"zip"),
+                ..CacheEntry::default()
+            },
+            changed: true,
+        };
+
+        let manifest =
+            Manifest::new(&H41Source, &download, &dir, &[H41_DATA_XML.to_string()]).unwrap();
+        assert_eq!(manifest.prepared.as_deref(), Some("2020-05-28T14:27:09Z"));
+        manifest.write(&dir).unwrap();
+        let manifest = Manifest::read(&dir).unwrap();
+        assert!(manifest.verify(&dir).is_empty());
+
+        fs::write(dir.join(H41_DATA_XML), "tampered").unwrap();
+        assert!(matches!(
+            manifest.verify(&dir)[..],
+            [Mismatch::Modified { .. }]
+        ));
+        fs::remove_file(dir.join(H41_DATA_XML)).unwrap();
+        assert_eq!(
+            manifest.verify(&dir),
+            vec![Mismatch::Missing(H41_DATA_XML.to_string())]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
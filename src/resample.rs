@@ -0,0 +1,363 @@
+use chrono::{Datelike, NaiveDate};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::series::TimeSeries;
+use crate::types::{BalanceSheet, ConceptType, ObservationMap, ObservationStatus};
+
+/// Calendar frequency observations are resampled to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Frequency {
+    /// calendar months.
+    Monthly,
+    /// calendar quarters.
+    Quarterly,
+    /// calendar years.
+    Annual,
+}
+
+impl Frequency {
+    /// Last day of the period holding `date`, used as the date of the period.
+    pub fn period_end(&self, date: &NaiveDate) -> NaiveDate {
+        let month = match self {
+            Frequency::Monthly => date.month(),
+            Frequency::Quarterly => (date.month() - 1) / 3 * 3 + 3,
+            Frequency::Annual => 12,
+        };
+        let (year, month) = if month == 12 {
+            (date.year() + 1, 1)
+        } else {
+            (date.year(), month + 1)
+        };
+        NaiveDate::from_ymd_opt(year, month, 1)
+            .and_then(|d| d.pred_opt())
+            .expect("valid period end")
+    }
+}
+
+impl fmt::Display for Frequency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let frequency = match self {
+            Frequency::Monthly => "monthly",
+            Frequency::Quarterly => "quarterly",
+            Frequency::Annual => "annual",
+        };
+        write!(f, "{frequency}")
+    }
+}
+
+impl FromStr for Frequency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Frequency, Self::Err> {
+        match s {
+            "monthly" | "m" => Ok(Frequency::Monthly),
+            "quarterly" | "q" => Ok(Frequency::Quarterly),
+            "annual" | "a" | "y" => Ok(Frequency::Annual),
+            _ => Err(format!("unknown frequency: {s}")),
+        }
+    }
+}
+
+/// How the values of a period are aggregated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Aggregation {
+    /// last value of the period.
+    #[default]
+    End,
+    /// average over the dates of the period with a value, rounded.
+    Mean,
+    /// largest value of the period.
+    Max,
+    /// smallest value of the period.
+    Min,
+}
+
+impl fmt::Display for Aggregation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let aggregation = match self {
+            Aggregation::End => "end",
+            Aggregation::Mean => "mean",
+            Aggregation::Max => "max",
+            Aggregation::Min => "min",
+        };
+        write!(f, "{aggregation}")
+    }
+}
+
+impl FromStr for Aggregation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Aggregation, Self::Err> {
+        match s {
+            "end" | "last" => Ok(Aggregation::End),
+            "mean" | "average" => Ok(Aggregation::Mean),
+            "max" => Ok(Aggregation::Max),
+            "min" => Ok(Aggregation::Min),
+            _ => Err(format!("unknown aggregation: {s}")),
+        }
+    }
+}
+
+impl Aggregation {
+    // Aggregate the values of a period, in date order, along with their
+    // status, skipping those without value. Averages over several values
+    // are derived, other aggregations keep the status of the value they
+    // pick.
+    fn apply(
+        &self,
+        values: impl Iterator<Item = (i64, ObservationStatus)>,
+    ) -> Option<(i64, ObservationStatus)> {
+        let values: Vec<(i64, ObservationStatus)> = values.filter(|(_, s)| s.has_value()).collect();
+        match self {
+            Aggregation::End => values.last().copied(),
+            Aggregation::Max => values.iter().rev().max_by_key(|(v, _)| *v).copied(),
+            Aggregation::Min => values.iter().rev().min_by_key(|(v, _)| *v).copied(),
+            Aggregation::Mean => match values.as_slice() {
+                [] => None,
+                [value] => Some(*value),
+                _ => {
+                    let sum: i64 = values.iter().map(|(v, _)| v).sum();
+                    Some((mean(sum, values.len()), ObservationStatus::Derived))
+                }
+            },
+        }
+    }
+}
+
+fn mean(sum: i64, dates: usize) -> i64 {
+    (sum as f64 / dates as f64).round() as i64
+}
+
+// Group dates by the end of their period.
+fn periods<'a, T>(
+    dates: impl Iterator<Item = (&'a NaiveDate, T)>,
+    frequency: Frequency,
+) -> BTreeMap<NaiveDate, Vec<T>> {
+    let mut periods: BTreeMap<NaiveDate, Vec<T>> = BTreeMap::new();
+    for (date, item) in dates {
+        periods
+            .entry(frequency.period_end(date))
+            .or_default()
+            .push(item);
+    }
+    periods
+}
+
+fn resample_period(period: &[&BalanceSheet], aggregation: Aggregation) -> BalanceSheet {
+    let mut bs = (*period.last().expect("period with observations")).clone();
+    for ctype in ConceptType::ALL.iter() {
+        let statement = bs.get_concept_mut(ctype);
+        statement.for_each_mut(|concept| {
+            let values = period
+                .iter()
+                .filter_map(|bs| bs.get(&concept.path))
+                .map(|c| (c.value, c.status));
+            match aggregation.apply(values) {
+                Some((value, status)) => {
+                    concept.value = value;
+                    concept.status = status;
+                }
+                None => concept.value = 0,
+            }
+        });
+        if aggregation == Aggregation::Mean {
+            statement.rollup();
+        }
+    }
+    bs
+}
+
+/// Resample observations to calendar periods, each one dated by its last
+/// day, see [`Frequency::period_end`].
+///
+/// The balance sheet of a period keeps the concept tree of its last date.
+/// Every concept is aggregated on its own from the dates it has a value
+/// on. Averages are rounded to the nearest unit and then rolled up, see
+/// [`Concept::rollup`](crate::types::Concept::rollup), so intermediate concepts without a series add up
+/// their children, while published totals keep their own average and may
+/// differ from the sum of their children by rounding. End of period values
+/// keep totals too, maximum and minimum values do not.
+pub fn resample(
+    obs: &ObservationMap,
+    frequency: Frequency,
+    aggregation: Aggregation,
+) -> ObservationMap {
+    periods(obs.iter(), frequency)
+        .into_iter()
+        .map(|(date, period)| (date, resample_period(&period, aggregation)))
+        .collect()
+}
+
+impl TimeSeries {
+    /// Resample the time series to calendar periods, see [`resample`].
+    pub fn resample(&self, frequency: Frequency, aggregation: Aggregation) -> TimeSeries {
+        let points = self
+            .dates
+            .iter()
+            .zip(self.values.iter().zip(self.statuses.iter()));
+        let mut series = TimeSeries {
+            path: self.path.clone(),
+            series_name: self.series_name.clone(),
+            ..TimeSeries::default()
+        };
+        for (date, period) in periods(points, frequency) {
+            let values = period
+                .iter()
+                .filter_map(|&(value, status)| Some(((*value)?, *status)));
+            let value = aggregation.apply(values);
+            series.dates.push(date);
+            series.values.push(value.map(|(v, _)| v));
+            series
+                .statuses
+                .push(value.map_or(ObservationStatus::Missing, |(_, s)| s));
+        }
+        series
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fed::parse_h41_data;
+    use crate::series::ObservationSeries;
+    use crate::types::{Concept, UNDEFINED_SERIES_NAME};
+    use std::fs;
+
+    #[test]
+    fn resample_test() {
+        let text = fs::read_to_string("tests/data/FRB_H41_2020.xml").unwrap();
+        let obs = parse_h41_data(&text).unwrap();
+        let date = |d: &str| NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap();
+        assert_eq!(
+            Frequency::Quarterly.period_end(&date("2020-05-27")),
+            date("2020-06-30")
+        );
+        assert_eq!(
+            Frequency::Monthly.period_end(&date("2020-12-02")),
+            date("2020-12-31")
+        );
+
+        let monthly = resample(&obs, Frequency::Monthly, Aggregation::End);
+        let end = date("2020-05-31");
+        let last = obs.range(..=end).next_back().unwrap().1;
+        assert_eq!(
+            serde_json::to_value(&monthly[&end]).unwrap(),
+            serde_json::to_value(last).unwrap()
+        );
+
+        let may: Vec<&BalanceSheet> = obs
+            .range(date("2020-05-01")..=end)
+            .map(|(_, bs)| bs)
+            .collect();
+        let mean = resample(&obs, Frequency::Monthly, Aggregation::Mean);
+        let capital = mean[&end].get("Capital").unwrap();
+        let values: Vec<i64> = may
+            .iter()
+            .map(|bs| bs.get("Capital").unwrap().value)
+            .collect();
+        let expected = (values.iter().sum::<i64>() as f64 / values.len() as f64).round() as i64;
+        assert_eq!(capital.value, expected);
+        assert_eq!(capital.status, ObservationStatus::Derived);
+        assert_eq!(
+            mean[&end].get_concept(&ConceptType::Capital).iter().count(),
+            last.get_concept(&ConceptType::Capital).iter().count()
+        );
+
+        let total = |c: &Concept| c.children().iter().map(|c| c.value).sum::<i64>();
+        let mut rolled_up = 0;
+        for ctype in ConceptType::ALL.iter() {
+            for concept in mean[&end].get_concept(ctype).iter() {
+                if !concept.is_leaf() && concept.series_name == UNDEFINED_SERIES_NAME {
+                    assert_eq!(concept.value, total(concept), "{}", concept.path);
+                    rolled_up += 1;
+                }
+            }
+        }
+        assert!(rolled_up > 0);
+
+        let path = "Assets/Liquidity and Credit Facilities/Corporate Credit Facilities LLC";
+        let mut gap = obs.clone();
+        let last_date = *obs.range(..=end).next_back().unwrap().0;
+        let missing = gap.get_mut(&last_date).unwrap().get_mut(path).unwrap();
+        missing.status = ObservationStatus::Missing;
+        let mean_of = |sum: i64, n: usize| (sum as f64 / n as f64).round() as i64;
+        let sum: i64 = may[..may.len() - 1]
+            .iter()
+            .map(|bs| bs.get(path).unwrap().value)
+            .sum();
+        let late = resample(&gap, Frequency::Monthly, Aggregation::Mean)[&end]
+            .get(path)
+            .unwrap()
+            .clone();
+        assert_eq!(late.value, mean_of(sum, may.len() - 1));
+        assert_eq!(late.status, ObservationStatus::Derived);
+
+        let max = resample(&obs, Frequency::Annual, Aggregation::Max);
+        let assets = obs.time_series("Assets").unwrap();
+        let year_max = assets.values.iter().flatten().max().copied();
+        assert_eq!(
+            max[&date("2020-12-31")].get("Assets").map(|c| c.value),
+            year_max
+        );
+        assert_eq!(
+            assets.resample(Frequency::Annual, Aggregation::Max).values,
+            vec![year_max]
+        );
+        assert_eq!(
+            assets
+                .resample(Frequency::Monthly, Aggregation::Mean)
+                .value(&end),
+            Some(mean[&end].get("Assets").unwrap().value)
+        );
+        assert_eq!("q".parse::<Frequency>(), Ok(Frequency::Quarterly));
+        assert!("weekly".parse::<Frequency>().is_err());
+    }
+
+    #[test]
+    fn mean_test() {
+        let bs = |surplus: Option<i64>, paid_in: i64| {
+            let mut capital = Concept::new("Capital", "C");
+            capital.insert_concept("Capital/Other", UNDEFINED_SERIES_NAME);
+            capital.insert_concept("Capital/Other/Surplus", "C1");
+            capital.insert_concept("Capital/Other/Paid in", "C2");
+            if let Some(surplus) = surplus {
+                capital.update_concept_observation(
+                    "Capital/Other/Surplus",
+                    surplus,
+                    ObservationStatus::Available,
+                );
+            }
+            capital.update_concept_observation(
+                "Capital/Other/Paid in",
+                paid_in,
+                ObservationStatus::Available,
+            );
+            capital.update_concept_observation(
+                "Capital",
+                surplus.unwrap_or_default() + paid_in,
+                ObservationStatus::Available,
+            );
+            BalanceSheet::new(
+                Concept::new("Assets", "A"),
+                Concept::new("Liabilities", "L"),
+                capital,
+            )
+        };
+        let date = |d: u32| NaiveDate::from_ymd_opt(2020, 5, d).unwrap();
+        let mut obs = ObservationMap::new();
+        obs.insert(date(6), bs(Some(10), 1));
+        obs.insert(date(13), bs(None, 2));
+        obs.insert(date(20), bs(Some(21), 4));
+
+        let mean = resample(&obs, Frequency::Monthly, Aggregation::Mean);
+        let month = &mean[&date(31)];
+        let value = |path: &str| month.get(path).map(|c| (c.value, c.status));
+        let derived = ObservationStatus::Derived;
+        assert_eq!(value("Capital/Other/Surplus"), Some((16, derived)));
+        assert_eq!(value("Capital/Other/Paid in"), Some((2, derived)));
+        assert_eq!(value("Capital/Other"), Some((18, derived)));
+        assert_eq!(value("Capital"), Some((13, derived)));
+    }
+}
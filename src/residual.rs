@@ -0,0 +1,168 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::types::{
+    Concept, ConceptType, ObservationMap, ObservationStatus, UNDEFINED_SERIES_NAME,
+};
+use crate::validate::Tolerance;
+
+/// Name of the residual leaves added under published totals.
+pub const RESIDUAL_CONCEPT_NAME: &str = "Unallocated";
+
+// Value of a concept its leaves add up to once residuals are added:
+// published totals in full, other intermediate concepts their children.
+// Residual leaves already added count as 0.
+fn covered(concept: &Concept) -> i64 {
+    if concept.status == ObservationStatus::Synthetic {
+        0
+    } else if concept.is_leaf() || concept.status == ObservationStatus::Available {
+        concept.value
+    } else {
+        concept.children().iter().map(covered).sum()
+    }
+}
+
+/// Path of the residual leaf of a concept.
+pub fn residual_path(parent: &str) -> String {
+    format!("{parent}/{RESIDUAL_CONCEPT_NAME}")
+}
+
+// Difference between a published total and the value its children add up
+// to, without residual leaves.
+fn residual(concept: &Concept) -> Option<i64> {
+    if concept.is_leaf() || concept.status != ObservationStatus::Available {
+        return None;
+    }
+    let children: i64 = concept.children().iter().map(covered).sum();
+    Some(concept.value - children)
+}
+
+/// Published totals, below `concept`, whose children do not add up to
+/// them along with the missing difference, e.g. because of filtered out
+/// or missing series. Residual leaves are left out, so the differences
+/// are the same once [`add_residuals`] is called.
+pub fn residuals(concept: &Concept) -> Vec<(String, i64)> {
+    concept
+        .iter()
+        .filter_map(|c| Some((c.path.clone(), residual(c)?)))
+        .filter(|(_, residual)| *residual != 0)
+        .collect()
+}
+
+/// Add a leaf named [`RESIDUAL_CONCEPT_NAME`] under every published total
+/// exceeding its children, beyond `tolerance`, on some date. The leaf
+/// holds the difference, so the leaves add up to the published totals
+/// where the children fall short of them.
+///
+/// Residual leaves are added to every date, keeping a single concept tree,
+/// with [`ObservationStatus::Synthetic`] status and 0 on the dates where
+/// nothing is missing or the children exceed the total. Calling it again
+/// leaves the residual leaves unchanged.
+pub fn add_residuals(obs: &mut ObservationMap, tolerance: &Tolerance) {
+    let parents: BTreeSet<String> = obs
+        .values()
+        .flat_map(|bs| {
+            ConceptType::ALL
+                .iter()
+                .flat_map(move |ctype| bs.get_concept(ctype).iter())
+        })
+        .filter(|c| residual(c).is_some_and(|r| r > 0 && !tolerance.allows(c.value, c.value - r)))
+        .map(|c| c.path.clone())
+        .collect();
+
+    for bs in obs.values_mut() {
+        let values: HashMap<String, i64> = ConceptType::ALL
+            .iter()
+            .flat_map(|ctype| residuals(bs.get_concept(ctype)))
+            .collect();
+        for parent in &parents {
+            let path = residual_path(parent);
+            let Some(statement) = ConceptType::from_path(parent) else {
+                continue;
+            };
+            let statement = bs.get_concept_mut(&statement);
+            if statement.get(&path).is_none() {
+                statement.insert_concept(&path, UNDEFINED_SERIES_NAME);
+            }
+            statement.update_concept_observation(
+                &path,
+                values.get(parent).copied().unwrap_or_default().max(0),
+                ObservationStatus::Synthetic,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fed::parse_h41_data;
+    use std::fs;
+
+    #[test]
+    fn add_residuals_test() {
+        let text = fs::read_to_string("tests/data/FRB_H41_2010.xml").unwrap();
+        let mut obs = parse_h41_data(&text).unwrap();
+        let date = *obs.keys().next().unwrap();
+        obs.get_mut(&date)
+            .unwrap()
+            .get_mut("Capital")
+            .unwrap()
+            .value += 100;
+
+        let tolerance = Tolerance::default();
+        add_residuals(&mut obs, &tolerance);
+        let path = residual_path("Capital");
+        assert!(obs[&date].get(&residual_path("Assets")).is_none());
+        for (d, bs) in &obs {
+            let leaf = bs.get(&path).unwrap();
+            assert_eq!(leaf.status, ObservationStatus::Synthetic);
+            if *d == date {
+                assert!(leaf.value >= 100);
+            } else {
+                assert!(leaf.value.abs() <= tolerance.absolute);
+            }
+            let capital = bs.get_concept(&ConceptType::Capital);
+            let children: i64 = capital.leaves().map(covered).sum();
+            assert_eq!(
+                capital.leaves().map(|c| c.value).sum::<i64>(),
+                capital.value.max(children)
+            );
+        }
+        let json = serde_json::to_value(&obs[&date]).unwrap();
+        assert!(json.to_string().contains("\"status\":\"synthetic\""));
+
+        let leaves = |obs: &ObservationMap| -> Vec<i64> {
+            obs.values()
+                .map(|bs| bs.get(&path).unwrap().value)
+                .collect()
+        };
+        let before = leaves(&obs);
+        add_residuals(&mut obs, &tolerance);
+        assert_eq!(leaves(&obs), before);
+        assert_eq!(
+            obs[&date]
+                .get_concept(&ConceptType::Capital)
+                .children()
+                .iter()
+                .filter(|c| c.path == path)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn negative_residuals_test() {
+        let text = fs::read_to_string("tests/data/FRB_H41_2010.xml").unwrap();
+        let mut obs = parse_h41_data(&text).unwrap();
+        let date = *obs.keys().next().unwrap();
+        let capital = obs.get_mut(&date).unwrap().get_mut("Capital").unwrap();
+        capital.value -= 100;
+        let expected = residual(capital).unwrap();
+        assert!(expected < -95);
+
+        add_residuals(&mut obs, &Tolerance::default());
+        assert!(obs[&date].get(&residual_path("Capital")).is_none());
+        assert!(residuals(obs[&date].get_concept(&ConceptType::Capital))
+            .contains(&("Capital".to_string(), expected)));
+    }
+}
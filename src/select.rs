@@ -0,0 +1,162 @@
+use chrono::NaiveDate;
+
+use crate::types::{ConceptType, ObservationMap};
+
+/// Selection of dates and concepts applied to observations, e.g. before exporting them.
+#[derive(Clone, Debug, Default)]
+pub struct Selection {
+    /// first date included.
+    pub from: Option<NaiveDate>,
+    /// last date included.
+    pub to: Option<NaiveDate>,
+    /// only these dates are included.
+    pub dates: Option<Vec<NaiveDate>>,
+    /// path of the concept whose branch is kept, e.g. "Assets/Securities Held Outright".
+    pub concept: Option<String>,
+    /// maximum depth of the kept concepts, 0 being the statements themselves.
+    pub depth: Option<usize>,
+}
+
+impl Selection {
+    fn includes(&self, date: &NaiveDate) -> bool {
+        self.from.is_none_or(|from| from <= *date)
+            && self.to.is_none_or(|to| *date <= to)
+            && self.dates.as_ref().is_none_or(|dates| dates.contains(date))
+    }
+
+    /// Statements, among the given ones, holding the selected concepts.
+    pub fn statements(&self, statements: &[ConceptType]) -> Vec<ConceptType> {
+        let selected = self.concept.as_deref().and_then(ConceptType::from_path);
+        statements
+            .iter()
+            .filter(|s| selected.is_none_or(|c| c == **s))
+            .copied()
+            .collect()
+    }
+
+    /// Return the selected observations, statements not holding the
+    /// selected concept are reduced to their total.
+    ///
+    /// Intermediate concepts cut into leaves by the depth are rolled up,
+    /// see [`crate::types::Concept::rollup`].
+    pub fn apply(
+        &self,
+        obs: &ObservationMap,
+    ) -> Result<ObservationMap, Box<dyn std::error::Error>> {
+        let concept = self.concept.as_deref();
+        let selected = match concept {
+            Some(path) => Some(
+                ConceptType::from_path(path).ok_or_else(|| format!("unknown concept: {path}"))?,
+            ),
+            None => None,
+        };
+
+        let mut selection = ObservationMap::new();
+        for (date, bs) in obs.iter().filter(|(date, _)| self.includes(date)) {
+            let mut bs = bs.clone();
+            for ctype in ConceptType::ALL {
+                let c = bs.get_concept_mut(&ctype);
+                let (path, depth) = match (concept, selected) {
+                    (Some(path), Some(s)) if s == ctype => {
+                        if !c.iter().any(|c| c.path == path) {
+                            return Err(format!("unknown concept: {path}").into());
+                        }
+                        (Some(path), self.depth)
+                    }
+                    (Some(_), _) => (None, Some(0)),
+                    (None, _) => (None, self.depth),
+                };
+                // Concepts becoming leaves keep the sum of the children cut off.
+                if let Some(depth) = depth {
+                    c.for_each_mut(|c| {
+                        if c.depth() == depth {
+                            c.rollup();
+                        }
+                    });
+                }
+                c.prune(path, depth);
+            }
+            selection.insert(*date, bs);
+        }
+        Ok(selection)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fed;
+
+    #[test]
+    fn apply_selection_test() {
+        let text = std::fs::read_to_string("tests/data/FRB_H41_2010.xml").unwrap();
+        let obs = fed::parse_h41_data(&text).unwrap();
+        let from = NaiveDate::from_ymd_opt(2010, 3, 3).unwrap();
+        let selection = Selection {
+            from: Some(from),
+            concept: Some("Assets/Securities Held Outright".to_string()),
+            depth: Some(2),
+            ..Selection::default()
+        };
+
+        let selected = selection.apply(&obs).unwrap();
+        assert_eq!(selected.keys().next(), Some(&from));
+        assert_eq!(selected.len(), obs.range(from..).count());
+        let assets = selected
+            .get(&from)
+            .unwrap()
+            .get_concept(&ConceptType::Assets);
+        let leaves: Vec<&str> = assets
+            .iter()
+            .filter(|c| c.is_leaf())
+            .map(|c| c.name())
+            .collect();
+        assert_eq!(
+            leaves,
+            vec![
+                "Federal agency debt securities",
+                "Mortgage-backed securities",
+                "U.S. Treasury securities"
+            ]
+        );
+        assert!(selected
+            .get(&from)
+            .unwrap()
+            .get_concept(&ConceptType::Capital)
+            .is_leaf());
+        assert_eq!(
+            selection.statements(&[ConceptType::Assets, ConceptType::Capital]),
+            vec![ConceptType::Assets]
+        );
+
+        let text = std::fs::read_to_string("tests/data/FRB_H41_2020.xml").unwrap();
+        let obs = fed::parse_h41_data(&text).unwrap();
+        let shallow = Selection {
+            concept: Some("Assets".to_string()),
+            depth: Some(1),
+            ..Selection::default()
+        };
+        let selected = shallow.apply(&obs).unwrap();
+        let (date, bs) = obs.iter().next_back().unwrap();
+        let mut rolled = bs.clone();
+        rolled.rollup();
+        let assets = selected[date].get_concept(&ConceptType::Assets);
+        assert!(assets.children().iter().all(|c| c.is_leaf()));
+        for path in [
+            "Assets/Central Bank Liquidity Swaps",
+            "Assets/Liquidity and Credit Facilities",
+            "Assets/Other",
+        ] {
+            let concept = assets.get(path).unwrap();
+            assert_ne!(concept.value, 0);
+            assert_eq!(concept.value, rolled.get(path).unwrap().value);
+        }
+        assert_eq!(assets.value, bs.get("Assets").unwrap().value);
+
+        let unknown = Selection {
+            concept: Some("Assets/Unknown".to_string()),
+            ..Selection::default()
+        };
+        assert!(unknown.apply(&obs).is_err());
+    }
+}
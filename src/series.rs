@@ -0,0 +1,245 @@
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+use crate::types::{Concept, ObservationMap, ObservationStatus};
+
+/// Values of a concept over time.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct TimeSeries {
+    /// accounting concept path.
+    pub path: String,
+    /// series the concept was extracted from.
+    pub series_name: String,
+    /// observation dates, in ascending order.
+    pub dates: Vec<NaiveDate>,
+    /// value at every date, `None` if the concept has no value at the date.
+    pub values: Vec<Option<i64>>,
+    /// status of the value at every date.
+    pub statuses: Vec<ObservationStatus>,
+}
+
+/// How to handle the dates a time series has no value for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MissingValues {
+    /// keep them as `None`.
+    Keep,
+    /// remove the dates.
+    Drop,
+    /// use 0.
+    Zero,
+    /// use the last previous value, dates before the first value are kept as `None`.
+    Forward,
+}
+
+impl TimeSeries {
+    fn push(&mut self, date: NaiveDate, concept: Option<&Concept>) {
+        self.dates.push(date);
+        self.values
+            .push(concept.filter(|c| c.status.has_value()).map(|c| c.value));
+        self.statuses
+            .push(concept.map_or(ObservationStatus::Missing, |c| c.status));
+    }
+
+    /// Number of dates.
+    pub fn len(&self) -> usize {
+        self.dates.len()
+    }
+
+    /// Return true if the time series has no dates.
+    pub fn is_empty(&self) -> bool {
+        self.dates.is_empty()
+    }
+
+    /// Value at a date, `None` if the date is not in the time series or
+    /// has no value.
+    pub fn value(&self, date: &NaiveDate) -> Option<i64> {
+        let i = self.dates.binary_search(date).ok()?;
+        self.values[i]
+    }
+
+    /// Iterate over the dates and values.
+    pub fn iter(&self) -> impl Iterator<Item = (NaiveDate, Option<i64>)> + '_ {
+        self.dates.iter().copied().zip(self.values.iter().copied())
+    }
+
+    /// Time series on the given dates, dates not in this one have no value.
+    pub fn reindex(&self, dates: &[NaiveDate]) -> TimeSeries {
+        let mut series = TimeSeries {
+            path: self.path.clone(),
+            series_name: self.series_name.clone(),
+            ..TimeSeries::default()
+        };
+        for date in dates {
+            let i = self.dates.binary_search(date).ok();
+            series.dates.push(*date);
+            series.values.push(i.and_then(|i| self.values[i]));
+            series
+                .statuses
+                .push(i.map_or(ObservationStatus::Missing, |i| self.statuses[i]));
+        }
+        series
+    }
+
+    /// Time series with the dates without value handled as given. Filled
+    /// values keep the status of the date they fill.
+    pub fn fill(&self, missing: MissingValues) -> TimeSeries {
+        let mut series = self.clone();
+        match missing {
+            MissingValues::Keep => {}
+            MissingValues::Drop => {
+                series = TimeSeries {
+                    path: self.path.clone(),
+                    series_name: self.series_name.clone(),
+                    ..TimeSeries::default()
+                };
+                for (i, value) in self.values.iter().enumerate() {
+                    if value.is_some() {
+                        series.dates.push(self.dates[i]);
+                        series.values.push(*value);
+                        series.statuses.push(self.statuses[i]);
+                    }
+                }
+            }
+            MissingValues::Zero => {
+                for value in series.values.iter_mut() {
+                    value.get_or_insert(0);
+                }
+            }
+            MissingValues::Forward => {
+                let mut last = None;
+                for value in series.values.iter_mut() {
+                    match value {
+                        Some(v) => last = Some(*v),
+                        None => *value = last,
+                    }
+                }
+            }
+        }
+        series
+    }
+}
+
+/// Align time series on the union of their dates, see [`TimeSeries::reindex`].
+pub fn align(series: &[TimeSeries]) -> Vec<TimeSeries> {
+    let dates: Vec<NaiveDate> = series
+        .iter()
+        .flat_map(|s| s.dates.iter().copied())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    series.iter().map(|s| s.reindex(&dates)).collect()
+}
+
+/// Time series of concepts extracted from observations.
+pub trait ObservationSeries {
+    /// Time series of the concept in `path` over every date, `None` if no
+    /// balance sheet holds the concept.
+    fn time_series(&self, path: &str) -> Option<TimeSeries>;
+
+    /// Time series of the first concept extracted from `series_name`, see
+    /// [`crate::BalanceSheet::get_by_series`].
+    fn time_series_by_series(&self, series_name: &str) -> Option<TimeSeries>;
+
+    /// Time series of several concepts, aligned on the union of their
+    /// dates, see [`align`].
+    fn time_series_many(
+        &self,
+        paths: &[&str],
+    ) -> Result<Vec<TimeSeries>, Box<dyn std::error::Error>>;
+}
+
+impl ObservationSeries for ObservationMap {
+    fn time_series(&self, path: &str) -> Option<TimeSeries> {
+        let concept = self.values().find_map(|bs| bs.get(path))?;
+        let mut series = TimeSeries {
+            path: concept.path.clone(),
+            series_name: concept.series_name.clone(),
+            ..TimeSeries::default()
+        };
+        for (date, bs) in self {
+            series.push(*date, bs.get(path));
+        }
+        Some(series)
+    }
+
+    fn time_series_by_series(&self, series_name: &str) -> Option<TimeSeries> {
+        let concept = self.values().find_map(|bs| bs.get_by_series(series_name))?;
+        let mut series = TimeSeries {
+            path: concept.path.clone(),
+            series_name: concept.series_name.clone(),
+            ..TimeSeries::default()
+        };
+        for (date, bs) in self {
+            series.push(*date, bs.get_by_series(series_name));
+        }
+        Some(series)
+    }
+
+    fn time_series_many(
+        &self,
+        paths: &[&str],
+    ) -> Result<Vec<TimeSeries>, Box<dyn std::error::Error>> {
+        let series = paths
+            .iter()
+            .map(|path| {
+                self.time_series(path)
+                    .ok_or_else(|| format!("unknown concept: {path}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(align(&series))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fed::{parse_h41_data, FED_ASSETS_SERIES_NAME};
+    use std::fs;
+
+    #[test]
+    fn time_series_test() {
+        let text = fs::read_to_string("tests/data/FRB_H41_2010.xml").unwrap();
+        let obs = parse_h41_data(&text).unwrap();
+
+        let assets = obs.time_series("Assets").unwrap();
+        assert_eq!(assets.len(), obs.len());
+        for (date, bs) in &obs {
+            assert_eq!(assets.value(date), Some(bs.get("Assets").unwrap().value));
+        }
+        assert_eq!(
+            obs.time_series_by_series(FED_ASSETS_SERIES_NAME),
+            Some(assets.clone())
+        );
+        assert!(obs.time_series("Assets/Unknown").is_none());
+        assert!(obs.time_series_many(&["Assets", "Unknown"]).is_err());
+
+        let many = obs.time_series_many(&["Assets", "Capital"]).unwrap();
+        assert_eq!(many[0].dates, many[1].dates);
+        assert_eq!(many[0], assets);
+
+        let missing = obs
+            .values()
+            .flat_map(|bs| bs.get_concept(&crate::ConceptType::Assets).leaves())
+            .find(|c| !c.status.has_value())
+            .unwrap()
+            .path
+            .clone();
+        let series = obs.time_series(&missing).unwrap();
+        assert!(series.values.contains(&None));
+        assert!(!series.fill(MissingValues::Zero).values.contains(&None));
+        assert!(series.fill(MissingValues::Drop).len() < series.len());
+
+        let mut short = assets.fill(MissingValues::Keep);
+        let last = short.dates.pop().unwrap();
+        short.values.pop();
+        short.statuses.pop();
+        let aligned = align(&[short, many[1].clone()]);
+        assert_eq!(aligned[0].len(), obs.len());
+        assert_eq!(aligned[0].value(&last), None);
+        assert_eq!(
+            aligned[0].fill(MissingValues::Forward).value(&last),
+            aligned[0].values[aligned[0].len() - 2]
+        );
+    }
+}
@@ -0,0 +1,341 @@
+use chrono::NaiveDate;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::columnar::{ConceptSchema, ObservationStore, SchemaConcept};
+use crate::export::columns::days_since_epoch;
+use crate::types::{ObservationMap, ObservationStatus};
+
+/// Bytes every snapshot starts with.
+pub const SNAPSHOT_MAGIC: &[u8; 8] = b"MLGNSNAP";
+/// Version of the snapshot format written by this library.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+const HEADER_LEN: usize = 40;
+const ALIGNMENT: usize = 8;
+const NO_PARENT: u32 = u32::MAX;
+
+fn status_code(status: ObservationStatus) -> u8 {
+    match status {
+        ObservationStatus::Missing => 0,
+        ObservationStatus::Available => 1,
+        ObservationStatus::NotAvailable => 2,
+        ObservationStatus::Derived => 3,
+        ObservationStatus::Synthetic => 4,
+    }
+}
+
+fn status_from_code(code: u8) -> Result<ObservationStatus, Box<dyn std::error::Error>> {
+    match code {
+        0 => Ok(ObservationStatus::Missing),
+        1 => Ok(ObservationStatus::Available),
+        2 => Ok(ObservationStatus::NotAvailable),
+        3 => Ok(ObservationStatus::Derived),
+        4 => Ok(ObservationStatus::Synthetic),
+        _ => Err(format!("invalid observation status code in snapshot: {code}").into()),
+    }
+}
+
+fn padding(len: usize) -> usize {
+    (ALIGNMENT - len % ALIGNMENT) % ALIGNMENT
+}
+
+fn write_str(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(&(s.len() as u32).to_le_bytes());
+    buf.extend_from_slice(s.as_bytes());
+}
+
+fn schema_bytes(schema: &ConceptSchema) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for c in schema.concepts() {
+        let parent = c.parent.map_or(NO_PARENT, |p| p as u32);
+        buf.extend_from_slice(&parent.to_le_bytes());
+        write_str(&mut buf, &c.path);
+        write_str(&mut buf, &c.series_name);
+        write_str(&mut buf, &c.currency);
+    }
+    buf
+}
+
+/// Write a columnar store as a snapshot.
+///
+/// The concept schema is stored once, followed by the dates and, for every
+/// date, the value and status of every concept in schema order. Values are
+/// little endian `i64` starting at an 8-byte aligned offset, so a memory
+/// mapped snapshot can be read in place with [`SnapshotView`].
+pub fn write_store_snapshot<W: Write>(
+    writer: &mut W,
+    store: &ObservationStore,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let schema = schema_bytes(store.schema());
+    let concepts = store.schema().concepts();
+
+    writer.write_all(SNAPSHOT_MAGIC)?;
+    writer.write_all(&SNAPSHOT_VERSION.to_le_bytes())?;
+    writer.write_all(&0u32.to_le_bytes())?;
+    writer.write_all(&(schema.len() as u64).to_le_bytes())?;
+    writer.write_all(&(store.len() as u64).to_le_bytes())?;
+    writer.write_all(&(concepts.len() as u64).to_le_bytes())?;
+    writer.write_all(&schema)?;
+    writer.write_all(&[0; ALIGNMENT][..padding(schema.len())])?;
+
+    for date in store.dates() {
+        writer.write_all(&days_since_epoch(date).to_le_bytes())?;
+    }
+    writer.write_all(&[0; ALIGNMENT][..padding(store.len() * 4)])?;
+
+    let columns: Vec<(&[i64], &[ObservationStatus])> = concepts
+        .iter()
+        .map(|c| {
+            (
+                store.column(&c.path).unwrap(),
+                store.status_column(&c.path).unwrap(),
+            )
+        })
+        .collect();
+    let mut statuses = Vec::with_capacity(store.len() * concepts.len());
+    for i in 0..store.len() {
+        for (values, status) in &columns {
+            writer.write_all(&values[i].to_le_bytes())?;
+            statuses.push(status_code(status[i]));
+        }
+    }
+    writer.write_all(&statuses)?;
+    Ok(())
+}
+
+/// Write the observations as a snapshot, see [`write_store_snapshot`].
+///
+/// Every balance sheet must share the concept tree of the first one.
+pub fn write_snapshot<W: Write>(
+    writer: &mut W,
+    obs: &ObservationMap,
+) -> Result<(), Box<dyn std::error::Error>> {
+    write_store_snapshot(writer, &ObservationStore::from_observations(obs)?)
+}
+
+/// Save the observations as a snapshot file, see [`write_snapshot`].
+pub fn save(path: &Path, obs: &ObservationMap) -> Result<(), Box<dyn std::error::Error>> {
+    let mut writer = io::BufWriter::new(fs::File::create(path)?);
+    write_snapshot(&mut writer, obs)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Load a snapshot file into a columnar store.
+pub fn load_store(path: &Path) -> Result<ObservationStore, Box<dyn std::error::Error>> {
+    SnapshotView::new(&fs::read(path)?)?.store()
+}
+
+/// Load the observations of a snapshot file.
+pub fn load(path: &Path) -> Result<ObservationMap, Box<dyn std::error::Error>> {
+    Ok(load_store(path)?.to_observations())
+}
+
+/// Read only view over the bytes of a snapshot, e.g. a memory mapped file,
+/// giving access to single values without building any balance sheet.
+pub struct SnapshotView<'a> {
+    schema: Arc<ConceptSchema>,
+    dates: Vec<NaiveDate>,
+    values: &'a [u8],
+    statuses: &'a [u8],
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Box<dyn std::error::Error>> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or("truncated snapshot")?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u32(&mut self) -> Result<u32, Box<dyn std::error::Error>> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn u64(&mut self) -> Result<usize, Box<dyn std::error::Error>> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into()?).try_into()?)
+    }
+
+    fn string(&mut self) -> Result<String, Box<dyn std::error::Error>> {
+        let len = self.u32()? as usize;
+        Ok(std::str::from_utf8(self.take(len)?)?.to_string())
+    }
+}
+
+impl<'a> SnapshotView<'a> {
+    /// Parse the header and schema of a snapshot, values are read on demand.
+    pub fn new(bytes: &'a [u8]) -> Result<SnapshotView<'a>, Box<dyn std::error::Error>> {
+        let mut reader = Reader { bytes, pos: 0 };
+        if reader.take(SNAPSHOT_MAGIC.len())? != SNAPSHOT_MAGIC {
+            return Err("not a mulligan snapshot".into());
+        }
+        let version = reader.u32()?;
+        if version != SNAPSHOT_VERSION {
+            return Err(format!("unsupported snapshot version: {version}").into());
+        }
+        reader.u32()?;
+        let schema_len = reader.u64()?;
+        let n_dates = reader.u64()?;
+        let n_concepts = reader.u64()?;
+        debug_assert_eq!(reader.pos, HEADER_LEN);
+
+        let mut schema = Reader {
+            bytes: reader.take(schema_len)?,
+            pos: 0,
+        };
+        let mut concepts = Vec::with_capacity(n_concepts.min(schema_len));
+        for i in 0..n_concepts {
+            let parent = match schema.u32()? {
+                NO_PARENT => None,
+                parent if (parent as usize) < i => Some(parent as usize),
+                parent => return Err(format!("invalid parent {parent} of concept {i}").into()),
+            };
+            concepts.push(SchemaConcept {
+                path: schema.string()?,
+                series_name: schema.string()?,
+                currency: schema.string()?,
+                parent,
+            });
+        }
+        reader.take(padding(schema_len))?;
+
+        let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+        let dates = reader
+            .take(n_dates.checked_mul(4).ok_or("truncated snapshot")?)?
+            .chunks_exact(4)
+            .map(|b| {
+                let days = i32::from_le_bytes(b.try_into().unwrap());
+                epoch
+                    .checked_add_signed(chrono::Duration::days(days.into()))
+                    .ok_or("invalid date in snapshot")
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        reader.take(padding(n_dates * 4))?;
+
+        let cells = n_dates
+            .checked_mul(n_concepts)
+            .ok_or("truncated snapshot")?;
+        let values = reader.take(cells.checked_mul(8).ok_or("truncated snapshot")?)?;
+        let statuses = reader.take(cells)?;
+
+        Ok(SnapshotView {
+            schema: Arc::new(ConceptSchema::new(concepts)?),
+            dates,
+            values,
+            statuses,
+        })
+    }
+
+    /// Concept schema of the snapshot.
+    pub fn schema(&self) -> &Arc<ConceptSchema> {
+        &self.schema
+    }
+
+    /// Concepts of the snapshot in column order.
+    pub fn concepts(&self) -> &[SchemaConcept] {
+        self.schema.concepts()
+    }
+
+    /// Dates of the snapshot in ascending order.
+    pub fn dates(&self) -> &[NaiveDate] {
+        &self.dates
+    }
+
+    /// Value and status of a concept at a date, given their indexes.
+    pub fn value(&self, date: usize, concept: usize) -> Option<(i64, ObservationStatus)> {
+        if date >= self.dates.len() || concept >= self.schema.len() {
+            return None;
+        }
+        let cell = date * self.schema.len() + concept;
+        let value = i64::from_le_bytes(self.values[cell * 8..cell * 8 + 8].try_into().unwrap());
+        let status = status_from_code(self.statuses[cell]).ok()?;
+        Some((value, status))
+    }
+
+    /// Copy the values of the snapshot into a columnar store.
+    pub fn store(&self) -> Result<ObservationStore, Box<dyn std::error::Error>> {
+        let mut store = ObservationStore::with_dates(self.schema.clone(), self.dates.clone());
+        for i in 0..self.dates.len() {
+            for c in 0..self.schema.len() {
+                let (value, status) = self.value(i, c).ok_or("invalid snapshot value")?;
+                store.set_at(i, c, value, status);
+            }
+        }
+        Ok(store)
+    }
+
+    /// Build the balance sheets of every date.
+    pub fn observations(&self) -> Result<ObservationMap, Box<dyn std::error::Error>> {
+        Ok(self.store()?.to_observations())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fed::parse_h41_data;
+
+    #[test]
+    fn snapshot_round_trip_test() {
+        let text = fs::read_to_string("tests/data/FRB_H41_2020.xml").unwrap();
+        let obs = parse_h41_data(&text).unwrap();
+
+        let mut bytes = Vec::new();
+        write_snapshot(&mut bytes, &obs).unwrap();
+        let view = SnapshotView::new(&bytes).unwrap();
+        assert_eq!(view.dates().len(), obs.len());
+        assert_eq!(view.concepts()[0].path, "Assets");
+        assert_eq!(view.concepts()[0].parent, None);
+        assert_eq!(view.concepts(), view.schema().concepts());
+        assert_eq!(
+            serde_json::to_value(view.observations().unwrap()).unwrap(),
+            serde_json::to_value(&obs).unwrap()
+        );
+
+        let concept = |path: &str, parent: Option<usize>| SchemaConcept {
+            path: path.to_string(),
+            series_name: "UNDEFINED".to_string(),
+            currency: "USD".to_string(),
+            parent,
+        };
+        let schema = ConceptSchema::new(vec![
+            concept("Capital", None),
+            concept("Liabilities", None),
+            concept("Assets", None),
+            concept("Assets/B", Some(2)),
+            concept("Assets/A", Some(2)),
+        ])
+        .unwrap();
+        let date = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        let mut store = ObservationStore::new(Arc::new(schema));
+        store.set(date, 0, 3, ObservationStatus::Available);
+        store.set(date, 3, 42, ObservationStatus::Available);
+        let mut reordered = Vec::new();
+        write_store_snapshot(&mut reordered, &store).unwrap();
+        let bs = SnapshotView::new(&reordered)
+            .unwrap()
+            .observations()
+            .unwrap()
+            .remove(&date)
+            .unwrap();
+        assert_eq!(bs.get("Capital").unwrap().value, 3);
+        assert_eq!(bs.get("Assets/B").unwrap().value, 42);
+        assert_eq!(bs.get("Assets/A").unwrap().value, 0);
+
+        bytes[8] = 2;
+        assert!(SnapshotView::new(&bytes).is_err());
+        assert!(SnapshotView::new(&bytes[..20]).is_err());
+    }
+}
@@ -0,0 +1,57 @@
+use crate::ecb::WfsSource;
+use crate::fed::H41Source;
+use crate::types::{ConceptType, ObservationMap};
+
+/// Statistical release that can be downloaded and parsed into balance sheets.
+pub trait DataSource {
+    /// Short identifier of the release, e.g. "h41".
+    fn id(&self) -> &'static str;
+
+    /// Human readable description of the release.
+    fn description(&self) -> &'static str;
+
+    /// URLs the release can be downloaded from, the first one is the default.
+    fn urls(&self) -> &'static [&'static str];
+
+    /// Files expected inside the downloaded zip archive,
+    /// empty if the release is not distributed as an archive.
+    fn archive_members(&self) -> &'static [&'static str];
+
+    /// File containing the observations, either an archive member or
+    /// the name given to the downloaded file.
+    fn data_file(&self) -> &'static str;
+
+    /// Statements filled in the parsed balance sheets.
+    fn statements(&self) -> &'static [ConceptType];
+
+    /// Time the data file was prepared by the publisher, as stated in its header.
+    fn prepared(&self, _text: &str) -> Option<String> {
+        None
+    }
+
+    /// Parse the data file contents to return an ordered map with a
+    /// balance sheet for each period of time.
+    fn parse(&self, text: &str) -> Result<ObservationMap, Box<dyn std::error::Error>>;
+}
+
+/// All the releases supported by the library.
+pub fn sources() -> Vec<&'static dyn DataSource> {
+    vec![&H41Source, &WfsSource]
+}
+
+/// Find a supported release by its identifier.
+pub fn find_source(id: &str) -> Option<&'static dyn DataSource> {
+    sources().into_iter().find(|s| s.id() == id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_source_test() {
+        assert_eq!(find_source("h41").unwrap().data_file(), "H41_data.xml");
+        assert!(find_source("ecb").unwrap().archive_members().is_empty());
+        assert!(find_source("unknown").is_none());
+    }
+}
@@ -0,0 +1,418 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::{params, Connection};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use crate::types::{
+    BalanceSheet, Concept, ConceptType, ObservationMap, PATH_SEPARATOR, UNDEFINED_SERIES_NAME,
+};
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+const VINTAGE_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS releases (
+    source TEXT NOT NULL,
+    vintage TEXT NOT NULL,
+    loaded_at TEXT NOT NULL,
+    PRIMARY KEY (source, vintage)
+);
+CREATE TABLE IF NOT EXISTS concepts (
+    id INTEGER PRIMARY KEY,
+    source TEXT NOT NULL,
+    path TEXT NOT NULL,
+    statement TEXT NOT NULL,
+    UNIQUE (source, path)
+);
+CREATE TABLE IF NOT EXISTS release_concepts (
+    source TEXT NOT NULL,
+    vintage TEXT NOT NULL,
+    concept_id INTEGER NOT NULL REFERENCES concepts(id),
+    series_name TEXT NOT NULL,
+    parent_id INTEGER REFERENCES concepts(id),
+    currency TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    PRIMARY KEY (source, vintage, concept_id)
+);
+CREATE TABLE IF NOT EXISTS observations (
+    concept_id INTEGER NOT NULL REFERENCES concepts(id),
+    date TEXT NOT NULL,
+    vintage TEXT NOT NULL,
+    value INTEGER NOT NULL,
+    status TEXT NOT NULL,
+    PRIMARY KEY (concept_id, date, vintage)
+);
+";
+
+const UPSERT_RELEASE: &str = "
+INSERT INTO releases (source, vintage, loaded_at) VALUES (?1, ?2, ?3)
+ON CONFLICT (source, vintage) DO UPDATE SET loaded_at = excluded.loaded_at";
+
+// Concepts are shared by every release, what a release may change about
+// them is kept in release_concepts.
+const UPSERT_CONCEPT: &str = "
+INSERT INTO concepts (source, path, statement) VALUES (?1, ?2, ?3)
+ON CONFLICT (source, path) DO UPDATE SET statement = excluded.statement
+RETURNING id";
+
+const DELETE_RELEASE_CONCEPTS: &str = "
+DELETE FROM release_concepts WHERE source = ?1 AND vintage = ?2";
+
+const INSERT_RELEASE_CONCEPT: &str = "
+INSERT INTO release_concepts (source, vintage, concept_id, series_name, parent_id, currency, position)
+VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)";
+
+const UPSERT_OBSERVATION: &str = "
+INSERT INTO observations (concept_id, date, vintage, value, status) VALUES (?1, ?2, ?3, ?4, ?5)
+ON CONFLICT (concept_id, date, vintage) DO UPDATE SET
+    value = excluded.value,
+    status = excluded.status";
+
+// Latest release not newer than the requested vintage.
+const SELECT_TREE_VINTAGE: &str = "
+SELECT MAX(vintage) FROM releases WHERE source = ?1 AND (?2 IS NULL OR vintage <= ?2)";
+
+const SELECT_CONCEPTS: &str = "
+SELECT c.path, rc.series_name, c.statement, rc.currency
+FROM release_concepts rc JOIN concepts c ON c.id = rc.concept_id
+WHERE rc.source = ?1 AND rc.vintage = ?2 ORDER BY rc.position";
+
+// Latest vintage of every observation not newer than the requested one,
+// for the concepts in the tree of the release ?3.
+const SELECT_OBSERVATIONS: &str = "
+SELECT c.path, c.statement, o.date, o.value, o.status
+FROM observations o JOIN concepts c ON c.id = o.concept_id
+JOIN release_concepts rc ON rc.concept_id = c.id AND rc.source = ?1 AND rc.vintage = ?3
+WHERE c.source = ?1 AND o.vintage = (
+    SELECT MAX(v.vintage) FROM observations v
+    WHERE v.concept_id = o.concept_id AND v.date = o.date
+    AND (?2 IS NULL OR v.vintage <= ?2)
+)";
+
+const SELECT_RELEASES: &str = "
+SELECT source, vintage, loaded_at FROM releases
+WHERE source = ?1 ORDER BY vintage";
+
+/// Release of a data source loaded into the store.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Release {
+    /// identifier of the data source.
+    pub source: String,
+    /// version of the published data, e.g. the time it was prepared,
+    /// normalized with [`normalize_vintage`].
+    pub vintage: String,
+    /// time the release was loaded into the store.
+    pub loaded_at: DateTime<Utc>,
+}
+
+/// Normalize a vintage, given as an RFC 3339 time or a date (taken at
+/// midnight), to a UTC time with second precision, e.g.
+/// "2020-05-28T14:27:09Z", so vintages sort chronologically as text.
+pub fn normalize_vintage(vintage: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let time = match DateTime::parse_from_rfc3339(vintage) {
+        Ok(time) => time.with_timezone(&Utc),
+        Err(_) => NaiveDate::parse_from_str(vintage, DATE_FORMAT)
+            .map_err(|_| format!("invalid vintage: {vintage}"))?
+            .and_hms_opt(0, 0, 0)
+            .expect("valid midnight")
+            .and_utc(),
+    };
+    Ok(time.format(VINTAGE_FORMAT).to_string())
+}
+
+/// SQLite database keeping every release of the observations of a source,
+/// so history can be queried with SQL and updated incrementally.
+///
+/// Observations are keyed by concept, date and vintage: loading the same
+/// vintage again updates it in place while a new vintage is kept along
+/// the previous ones. The concept tree is kept per release too, so
+/// concepts added or dropped by a release do not show up in the others.
+pub struct SqliteStore {
+    conn: Connection,
+}
+
+impl SqliteStore {
+    /// Open or create a store in the given database file.
+    pub fn open(path: &Path) -> Result<SqliteStore, Box<dyn std::error::Error>> {
+        SqliteStore::with_connection(Connection::open(path)?)
+    }
+
+    /// Create a store kept in memory.
+    pub fn open_in_memory() -> Result<SqliteStore, Box<dyn std::error::Error>> {
+        SqliteStore::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<SqliteStore, Box<dyn std::error::Error>> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(SqliteStore { conn })
+    }
+
+    /// Insert or update the observations of a release of `source`, see
+    /// [`normalize_vintage`] for the accepted vintages.
+    pub fn upsert(
+        &mut self,
+        source: &str,
+        vintage: &str,
+        obs: &ObservationMap,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let vintage = normalize_vintage(vintage)?;
+        let tx = self.conn.transaction()?;
+        tx.execute(UPSERT_RELEASE, params![source, vintage, Utc::now()])?;
+
+        tx.execute(DELETE_RELEASE_CONCEPTS, params![source, vintage])?;
+
+        let mut concept_ids: HashMap<String, i64> = HashMap::new();
+        if let Some(bs) = obs.values().next() {
+            let mut upsert_concept = tx.prepare_cached(UPSERT_CONCEPT)?;
+            let mut insert_release_concept = tx.prepare_cached(INSERT_RELEASE_CONCEPT)?;
+            let concepts = ConceptType::ALL
+                .iter()
+                .flat_map(|ctype| bs.get_concept(ctype).iter().map(move |c| (ctype, c)));
+            for (position, (statement, c)) in concepts.enumerate() {
+                let parent_id = c
+                    .path
+                    .rsplit_once(PATH_SEPARATOR)
+                    .and_then(|(parent, _)| concept_ids.get(parent));
+                let id: i64 = upsert_concept
+                    .query_row(params![source, c.path, statement.to_string()], |row| {
+                        row.get(0)
+                    })?;
+                insert_release_concept.execute(params![
+                    source,
+                    vintage,
+                    id,
+                    c.series_name,
+                    parent_id,
+                    c.currency,
+                    position as i64,
+                ])?;
+                concept_ids.insert(c.path.clone(), id);
+            }
+        }
+
+        {
+            let mut upsert_observation = tx.prepare_cached(UPSERT_OBSERVATION)?;
+            for (date, bs) in obs {
+                let date = date.format(DATE_FORMAT).to_string();
+                for c in ConceptType::ALL
+                    .iter()
+                    .flat_map(|ctype| bs.get_concept(ctype).iter())
+                {
+                    let id = concept_ids
+                        .get(&c.path)
+                        .ok_or_else(|| format!("concept missing from first date: {}", c.path))?;
+                    upsert_observation.execute(params![
+                        id,
+                        date,
+                        vintage,
+                        c.value,
+                        c.status.to_string()
+                    ])?;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Releases of `source` loaded into the store, oldest vintage first.
+    pub fn releases(&self, source: &str) -> Result<Vec<Release>, Box<dyn std::error::Error>> {
+        let mut stmt = self.conn.prepare(SELECT_RELEASES)?;
+        let releases = stmt
+            .query_map(params![source], |row| {
+                Ok(Release {
+                    source: row.get(0)?,
+                    vintage: row.get(1)?,
+                    loaded_at: row.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(releases)
+    }
+
+    fn balance_sheet_template(
+        &self,
+        source: &str,
+        vintage: &str,
+    ) -> Result<BalanceSheet, Box<dyn std::error::Error>> {
+        let mut roots: BTreeMap<String, Concept> = BTreeMap::new();
+        let mut stmt = self.conn.prepare(SELECT_CONCEPTS)?;
+        let mut rows = stmt.query(params![source, vintage])?;
+        while let Some(row) = rows.next()? {
+            let path: String = row.get(0)?;
+            let series_name: String = row.get(1)?;
+            let statement: String = row.get(2)?;
+            match roots.get_mut(&statement) {
+                Some(root) => root.insert_concept(&path, &series_name),
+                None => {
+                    let mut root = Concept::new(&path, &series_name);
+                    root.currency = row.get(3)?;
+                    roots.insert(statement, root);
+                }
+            }
+        }
+
+        let mut root = |ctype: ConceptType| {
+            let path = ctype.to_string();
+            roots
+                .remove(&path)
+                .unwrap_or_else(|| Concept::new(&path, UNDEFINED_SERIES_NAME))
+        };
+        Ok(BalanceSheet::new(
+            root(ConceptType::Assets),
+            root(ConceptType::Liabilities),
+            root(ConceptType::Capital),
+        ))
+    }
+
+    /// Load the observations of `source` into balance sheets, using for
+    /// every observation its latest vintage not newer than `vintage`,
+    /// or its latest vintage if none is given.
+    ///
+    /// Balance sheets follow the concept tree of the latest release not
+    /// newer than `vintage`.
+    pub fn load(
+        &self,
+        source: &str,
+        vintage: Option<&str>,
+    ) -> Result<ObservationMap, Box<dyn std::error::Error>> {
+        let vintage = vintage.map(normalize_vintage).transpose()?;
+        let mut obs: ObservationMap = BTreeMap::new();
+        let tree_vintage: Option<String> =
+            self.conn
+                .query_row(SELECT_TREE_VINTAGE, params![source, vintage], |row| {
+                    row.get(0)
+                })?;
+        let Some(tree_vintage) = tree_vintage else {
+            return Ok(obs);
+        };
+        let bs_template = self.balance_sheet_template(source, &tree_vintage)?;
+        let mut stmt = self.conn.prepare(SELECT_OBSERVATIONS)?;
+        let mut rows = stmt.query(params![source, vintage, tree_vintage])?;
+        while let Some(row) = rows.next()? {
+            let path: String = row.get(0)?;
+            let statement: String = row.get(1)?;
+            let date: String = row.get(2)?;
+            let status: String = row.get(4)?;
+            obs.entry(NaiveDate::parse_from_str(&date, DATE_FORMAT)?)
+                .or_insert_with(|| bs_template.clone())
+                .get_concept_mut(&statement.parse()?)
+                .update_concept_observation(&path, row.get(3)?, status.parse()?);
+        }
+        Ok(obs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fed::parse_h41_data;
+    use crate::types::ObservationStatus;
+    use std::fs;
+
+    #[test]
+    fn sqlite_store_test() {
+        let text = fs::read_to_string("tests/data/FRB_H41_2010.xml").unwrap();
+        let mut obs = parse_h41_data(&text).unwrap();
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        store.upsert("h41", "2010-06-01", &obs).unwrap();
+        store.upsert("h41", "2010-06-01", &obs).unwrap();
+
+        let loaded = store.load("h41", None).unwrap();
+        assert_eq!(
+            serde_json::to_value(&loaded).unwrap(),
+            serde_json::to_value(&obs).unwrap()
+        );
+
+        let date = *obs.keys().next().unwrap();
+        let path = "Capital/Capital paid in";
+        obs.get_mut(&date)
+            .unwrap()
+            .get_concept_mut(&ConceptType::Capital)
+            .update_concept_observation(path, 1, ObservationStatus::Available);
+        store.upsert("h41", "2010-06-08", &obs).unwrap();
+        assert_eq!(store.releases("h41").unwrap().len(), 2);
+
+        let value = |obs: &ObservationMap| obs[&date].get(path).unwrap().value;
+        assert_eq!(value(&store.load("h41", None).unwrap()), 1);
+        assert_eq!(
+            value(&store.load("h41", Some("2010-06-01")).unwrap()),
+            25651
+        );
+        assert!(store.load("ecb", None).unwrap().is_empty());
+
+        store
+            .upsert("h41", "2010-06-08T00:00:00+00:00", &obs)
+            .unwrap();
+        store
+            .upsert("h41", "2010-06-08T02:00:00.5+02:00", &obs)
+            .unwrap();
+        let vintages: Vec<String> = store
+            .releases("h41")
+            .unwrap()
+            .into_iter()
+            .map(|r| r.vintage)
+            .collect();
+        assert_eq!(vintages, ["2010-06-01T00:00:00Z", "2010-06-08T00:00:00Z"]);
+        assert!(store.upsert("h41", "latest", &obs).is_err());
+    }
+
+    #[test]
+    fn sqlite_store_tree_test() {
+        let text = fs::read_to_string("tests/data/FRB_H41_2010.xml").unwrap();
+        let obs = parse_h41_data(&text).unwrap();
+        let with = |path: &str| {
+            let mut obs = obs.clone();
+            for bs in obs.values_mut() {
+                bs.get_concept_mut(&ConceptType::Capital)
+                    .insert_concept(path, UNDEFINED_SERIES_NAME);
+            }
+            obs
+        };
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        store
+            .upsert("h41", "2010-06-01", &with("Capital/Dropped"))
+            .unwrap();
+        let added = with("Capital/Added");
+        store.upsert("h41", "2010-06-08", &added).unwrap();
+
+        let paths = |obs: &ObservationMap| -> Vec<String> {
+            let bs = obs.values().next().unwrap();
+            bs.get_concept(&ConceptType::Capital)
+                .iter()
+                .map(|c| c.path.clone())
+                .collect()
+        };
+        let latest = store.load("h41", None).unwrap();
+        assert_eq!(paths(&latest), paths(&added));
+        assert_eq!(
+            serde_json::to_value(&latest).unwrap(),
+            serde_json::to_value(&added).unwrap()
+        );
+        let first = store.load("h41", Some("2010-06-01")).unwrap();
+        assert_eq!(paths(&first), paths(&with("Capital/Dropped")));
+        assert!(store.load("h41", Some("2010-05-01")).unwrap().is_empty());
+
+        // Loading an older release again keeps the series of the newer one.
+        let path = "Capital/Capital paid in";
+        let mut renamed = with("Capital/Dropped");
+        for bs in renamed.values_mut() {
+            bs.get_mut(path).unwrap().series_name = "RENAMED".to_string();
+        }
+        store.upsert("h41", "2010-06-01", &renamed).unwrap();
+        let series = |obs: &ObservationMap| {
+            obs.values()
+                .next()
+                .unwrap()
+                .get(path)
+                .unwrap()
+                .series_name
+                .clone()
+        };
+        assert_eq!(series(&store.load("h41", None).unwrap()), series(&added));
+        assert_eq!(
+            series(&store.load("h41", Some("2010-06-01")).unwrap()),
+            "RENAMED"
+        );
+    }
+}
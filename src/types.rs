@@ -1,5 +1,13 @@
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+
+use crate::index::ConceptIndex;
+use crate::iter::{BreadthFirst, Iter, Leaves, PostOrder, VisitorMut, WithParents};
 
 pub(crate) const ASSETS_PATH: &str = "Assets";
 pub(crate) const LIABILITIES_PATH: &str = "Liabilities";
@@ -8,13 +16,72 @@ pub(crate) const CAPITAL_PATH: &str = "Capital";
 /// Use for intermediate series that contains children but no data.
 pub const UNDEFINED_SERIES_NAME: &str = "UNDEFINED";
 
-const PATH_SEPARATOR: char = '/';
+pub(crate) const PATH_SEPARATOR: char = '/';
+
+/// Status of the value of a concept as published by the source.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ObservationStatus {
+    /// no observation was published, e.g. intermediate concepts.
+    #[default]
+    Missing,
+    /// value published by the source.
+    Available,
+    /// observation published as not available, its value is 0.
+    NotAvailable,
+    /// value computed from other concepts, e.g. by [`Concept::rollup`].
+    Derived,
+    /// value of a concept not published by the source, e.g. residual leaves
+    /// added by [`crate::residual::add_residuals`].
+    Synthetic,
+}
+
+impl ObservationStatus {
+    /// Return true if the status comes with a value: published, derived
+    /// or synthetic.
+    pub fn has_value(&self) -> bool {
+        matches!(
+            self,
+            ObservationStatus::Available
+                | ObservationStatus::Derived
+                | ObservationStatus::Synthetic
+        )
+    }
+}
+
+impl fmt::Display for ObservationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            ObservationStatus::Missing => "missing",
+            ObservationStatus::Available => "available",
+            ObservationStatus::NotAvailable => "not_available",
+            ObservationStatus::Derived => "derived",
+            ObservationStatus::Synthetic => "synthetic",
+        };
+        write!(f, "{status}")
+    }
+}
+
+impl FromStr for ObservationStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ObservationStatus, Self::Err> {
+        match s {
+            "missing" => Ok(ObservationStatus::Missing),
+            "available" => Ok(ObservationStatus::Available),
+            "not_available" => Ok(ObservationStatus::NotAvailable),
+            "derived" => Ok(ObservationStatus::Derived),
+            "synthetic" => Ok(ObservationStatus::Synthetic),
+            _ => Err(format!("unknown observation status: {s}")),
+        }
+    }
+}
 
 /// Accounting concept.
 /// A concept follows a tree structure where the leaf nodes contains
 /// the final accounting values
 /// and the intermediates nodes group leaves or non-leaves nodes.
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Concept {
     /// accouting concept path, string separated by '/'.
     pub path: String,
@@ -22,7 +89,18 @@ pub struct Concept {
     pub series_name: String,
     /// accounting concept value.
     pub value: i64,
+    /// currency the value is expressed in (in millions), e.g. "USD".
+    #[serde(default)]
+    pub currency: String,
+    /// status of the accounting concept value.
+    #[serde(default)]
+    pub status: ObservationStatus,
     pub(crate) children: Vec<Concept>,
+    /// index of the concepts below, built on the first lookup and
+    /// shared by clones. Every method that may change the tree below,
+    /// e.g. [`Concept::get_mut`] or [`Concept::walk_mut`], drops it.
+    #[serde(skip)]
+    pub(crate) index: OnceLock<Arc<ConceptIndex>>,
 }
 
 impl Concept {
@@ -40,10 +118,18 @@ impl Concept {
             path: path.to_string(),
             series_name: series.to_string(),
             value: 0,
+            currency: String::new(),
+            status: ObservationStatus::default(),
             children: Vec::new(),
+            index: OnceLock::new(),
         }
     }
 
+    /// Depth of the concept in its statement tree, 0 for the statement itself.
+    pub fn depth(&self) -> usize {
+        self.path.matches(PATH_SEPARATOR).count()
+    }
+
     /// Return true if concept is a leaf concept.
     pub fn is_leaf(&self) -> bool {
         self.children.is_empty()
@@ -51,7 +137,14 @@ impl Concept {
 
     /// Insert a concept inside the concept tree structure
     /// creating intermediate concepts if necessary.
+    /// New concepts inherit the currency of the concept they are inserted into.
     pub fn insert_concept(&mut self, path: &str, series: &str) {
+        self.index.take();
+        let currency = self.currency.clone();
+        let new_concept = |path: &str, series: &str| Concept {
+            currency: currency.clone(),
+            ..Concept::new(path, series)
+        };
         let mut concept = self;
         for i in path
             .char_indices()
@@ -75,47 +168,192 @@ impl Concept {
                 } else {
                     concept
                         .children
-                        .push(Concept::new(&path[..i], UNDEFINED_SERIES_NAME));
+                        .push(new_concept(&path[..i], UNDEFINED_SERIES_NAME));
                     concept = concept.children.last_mut().unwrap();
                 }
             }
         }
-        concept.children.push(Concept::new(path, series));
+        concept.children.push(new_concept(path, series));
     }
 
     /// update the concept specified by path with its accounting value.
     pub fn update_concept_value(&mut self, path: &str, value: i64) {
+        self.find_concept_mut(path).value = value;
+    }
+
+    /// Update the value of the concept in `path` along with its status.
+    pub fn update_concept_observation(
+        &mut self,
+        path: &str,
+        value: i64,
+        status: ObservationStatus,
+    ) {
+        let concept = self.find_concept_mut(path);
+        concept.value = value;
+        concept.status = status;
+    }
+
+    // Concept in `path` for its value to be updated, keeping the index as
+    // the tree does not change.
+    fn find_concept_mut(&mut self, path: &str) -> &mut Concept {
         if path == ASSETS_PATH || path == LIABILITIES_PATH || path == CAPITAL_PATH {
-            self.value = value;
-            return;
+            return self;
         }
-        let mut concept = self;
-        for i in path
-            .char_indices()
-            .filter(|c| c.1 == PATH_SEPARATOR)
-            .map(|v| v.0)
-        {
-            if concept.path == path[..i] {
-                continue;
-            } else {
-                concept = concept
-                    .children
-                    .iter_mut()
-                    .find(|c| c.path == path[..i])
-                    .unwrap();
-            }
+        let route = self
+            .route(path)
+            .unwrap_or_else(|| panic!("concept not found: {path}"))
+            .into_owned();
+        route
+            .iter()
+            .fold(self, |concept, i| &mut concept.children[*i])
+    }
+
+    fn index(&self) -> &ConceptIndex {
+        self.index.get_or_init(|| Arc::new(ConceptIndex::new(self)))
+    }
+
+    // Positions of the children to follow from this concept to `path`.
+    fn route(&self, path: &str) -> Option<Cow<'_, [usize]>> {
+        let route = self.index().path(path)?;
+        debug_assert!(self.follow(route).is_some_and(|c| c.path == path));
+        Some(Cow::Borrowed(route))
+    }
+
+    fn follow(&self, route: &[usize]) -> Option<&Concept> {
+        route
+            .iter()
+            .try_fold(self, |concept, i| concept.children.get(*i))
+    }
+
+    /// Get the concept in `path` below this one, or this concept itself.
+    pub fn get(&self, path: &str) -> Option<&Concept> {
+        self.follow(&self.route(path)?)
+    }
+
+    /// Get a mutable reference to the concept in `path` below this one,
+    /// or to this concept itself.
+    ///
+    /// The concept may be changed in any way, so the index of every
+    /// concept on its way is dropped.
+    pub fn get_mut(&mut self, path: &str) -> Option<&mut Concept> {
+        let route = self.route(path)?.into_owned();
+        self.index.take();
+        Some(route.iter().fold(self, |concept, i| {
+            let child = &mut concept.children[*i];
+            child.index.take();
+            child
+        }))
+    }
+
+    /// Get the first concept, in tree order, extracted from `series_name`.
+    pub fn get_by_series(&self, series_name: &str) -> Option<&Concept> {
+        let concept = self.follow(self.index().series(series_name)?)?;
+        debug_assert_eq!(concept.series_name, series_name);
+        Some(concept)
+    }
+
+    /// Remove the concepts outside the branch of `path` (keeping its
+    /// ancestors so the tree remains rooted at its statement) and the
+    /// concepts deeper than `max_depth`.
+    pub fn prune(&mut self, path: Option<&str>, max_depth: Option<usize>) {
+        self.index.take();
+        self.children.retain(|c| {
+            let in_branch = path.is_none_or(|p| {
+                let ancestor_or_self = p == c.path
+                    || (p.starts_with(&c.path) && p[c.path.len()..].starts_with(PATH_SEPARATOR));
+                let descendant =
+                    c.path.starts_with(p) && c.path[p.len()..].starts_with(PATH_SEPARATOR);
+                ancestor_or_self || descendant
+            });
+            in_branch && max_depth.is_none_or(|d| c.depth() <= d)
+        });
+        for child in &mut self.children {
+            child.prune(path, max_depth);
         }
-        concept
-            .children
-            .iter_mut()
-            .find(|c| c.path == path)
-            .unwrap()
-            .value = value;
+    }
+
+    /// Children of the concept, in the order they were inserted.
+    pub fn children(&self) -> &[Concept] {
+        &self.children
     }
 
     /// Provides the iterator over concepts
-    pub fn iter(&self) -> crate::iter::Iter<'_> {
-        crate::iter::Iter::new(self)
+    pub fn iter(&self) -> Iter<'_> {
+        Iter::new(self)
+    }
+
+    /// Iterate over the leaf concepts, in pre-order.
+    pub fn leaves(&self) -> Leaves<'_> {
+        Leaves::new(self)
+    }
+
+    /// Iterate over the concepts visiting children before their parent.
+    pub fn post_order(&self) -> PostOrder<'_> {
+        PostOrder::new(self)
+    }
+
+    /// Iterate over the concepts level by level.
+    pub fn breadth_first(&self) -> BreadthFirst<'_> {
+        BreadthFirst::new(self)
+    }
+
+    /// Iterate in pre-order over the concepts along with their depth below
+    /// this concept and their parent, `None` for this concept.
+    pub fn iter_with_parents(&self) -> WithParents<'_> {
+        WithParents::new(self)
+    }
+
+    /// Walk the concept tree calling `visitor` on every concept when it is
+    /// entered and, once its children have been walked, when it is left.
+    pub fn walk_mut<V: VisitorMut>(&mut self, visitor: &mut V) {
+        self.index.take();
+        visitor.enter(self);
+        for child in self.children.iter_mut() {
+            child.walk_mut(visitor);
+        }
+        visitor.leave(self);
+    }
+
+    /// Fill the value of the intermediate concepts without series, as
+    /// created by [`Concept::insert_concept`], with the sum of their
+    /// children, marking them as [`ObservationStatus::Derived`].
+    ///
+    /// Intermediate concepts are summed after their children, so nested
+    /// ones add up every descendant.
+    ///
+    /// Only values change, so the lookup index is kept.
+    pub fn rollup(&mut self) {
+        for child in self.children.iter_mut() {
+            child.rollup();
+        }
+        if !self.is_leaf() && self.series_name == UNDEFINED_SERIES_NAME {
+            self.value = self.children.iter().map(|c| c.value).sum();
+            self.status = ObservationStatus::Derived;
+        }
+    }
+
+    /// Call `f` on every concept, in pre-order.
+    pub fn for_each_mut<F: FnMut(&mut Concept)>(&mut self, f: F) {
+        struct ForEach<F>(F);
+        impl<F: FnMut(&mut Concept)> VisitorMut for ForEach<F> {
+            fn enter(&mut self, concept: &mut Concept) {
+                (self.0)(concept)
+            }
+        }
+        self.walk_mut(&mut ForEach(f));
+    }
+}
+
+impl fmt::Debug for Concept {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Concept")
+            .field("path", &self.path)
+            .field("series_name", &self.series_name)
+            .field("value", &self.value)
+            .field("currency", &self.currency)
+            .field("status", &self.status)
+            .field("children", &self.children)
+            .finish_non_exhaustive()
     }
 }
 
@@ -149,6 +387,7 @@ impl fmt::Display for Concept {
 }
 
 /// Type of balance sheet concepts
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
 pub enum ConceptType {
     /// Assets in balance sheet
     Assets,
@@ -158,6 +397,52 @@ pub enum ConceptType {
     Capital,
 }
 
+impl ConceptType {
+    /// Every statement, in balance sheet order.
+    pub(crate) const ALL: [ConceptType; 3] = [
+        ConceptType::Assets,
+        ConceptType::Liabilities,
+        ConceptType::Capital,
+    ];
+
+    /// Concept type of the statement a concept path belongs to.
+    pub fn from_path(path: &str) -> Option<ConceptType> {
+        match path.split(PATH_SEPARATOR).next()? {
+            ASSETS_PATH => Some(ConceptType::Assets),
+            LIABILITIES_PATH => Some(ConceptType::Liabilities),
+            CAPITAL_PATH => Some(ConceptType::Capital),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ConceptType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConceptType::Assets => write!(f, "{ASSETS_PATH}"),
+            ConceptType::Liabilities => write!(f, "{LIABILITIES_PATH}"),
+            ConceptType::Capital => write!(f, "{CAPITAL_PATH}"),
+        }
+    }
+}
+
+impl FromStr for ConceptType {
+    type Err = String;
+
+    /// Parse a concept type from its name, ignoring case.
+    fn from_str(s: &str) -> Result<ConceptType, Self::Err> {
+        if s.eq_ignore_ascii_case(ASSETS_PATH) {
+            Ok(ConceptType::Assets)
+        } else if s.eq_ignore_ascii_case(LIABILITIES_PATH) {
+            Ok(ConceptType::Liabilities)
+        } else if s.eq_ignore_ascii_case(CAPITAL_PATH) {
+            Ok(ConceptType::Capital)
+        } else {
+            Err(format!("unknown concept type: {s}"))
+        }
+    }
+}
+
 /// Balance sheet containing assets, liabilities and capital.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct BalanceSheet {
@@ -168,12 +453,19 @@ pub struct BalanceSheet {
 
 impl BalanceSheet {
     /// Create new balance sheet from concepts.
+    ///
+    /// The statements are indexed up front, so balance sheets cloned from
+    /// a template share its index.
     pub fn new(assets: Concept, liabilities: Concept, capital: Concept) -> BalanceSheet {
-        BalanceSheet {
+        let bs = BalanceSheet {
             assets,
             liabilities,
             capital,
+        };
+        for concept in [&bs.assets, &bs.liabilities, &bs.capital] {
+            concept.index();
         }
+        bs
     }
 
     /// Get a reference to a concrete concept.
@@ -193,8 +485,37 @@ impl BalanceSheet {
             ConceptType::Capital => &mut self.capital,
         }
     }
+
+    /// Roll up the intermediate concepts of every statement, see [`Concept::rollup`].
+    pub fn rollup(&mut self) {
+        self.assets.rollup();
+        self.liabilities.rollup();
+        self.capital.rollup();
+    }
+
+    /// Get the concept in `path`, looked up in the statement named by its root.
+    pub fn get(&self, path: &str) -> Option<&Concept> {
+        self.get_concept(&ConceptType::from_path(path)?).get(path)
+    }
+
+    /// Get a mutable reference to the concept in `path`, see [`BalanceSheet::get`].
+    pub fn get_mut(&mut self, path: &str) -> Option<&mut Concept> {
+        self.get_concept_mut(&ConceptType::from_path(path)?)
+            .get_mut(path)
+    }
+
+    /// Get the first concept extracted from `series_name` in assets,
+    /// liabilities or capital.
+    pub fn get_by_series(&self, series_name: &str) -> Option<&Concept> {
+        [&self.assets, &self.liabilities, &self.capital]
+            .into_iter()
+            .find_map(|c| c.get_by_series(series_name))
+    }
 }
 
+/// Ordered map containing balance sheet grouped for each date.
+pub type ObservationMap = BTreeMap<NaiveDate, BalanceSheet>;
+
 impl fmt::Display for BalanceSheet {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -263,4 +584,85 @@ mod tests {
             8
         );
     }
+
+    #[test]
+    fn get_test() {
+        let text = std::fs::read_to_string("tests/data/FRB_H41_2020.xml").unwrap();
+        let mut obs = fed::parse_h41_data(&text).unwrap();
+        let bs = obs.values_mut().next().unwrap();
+        let path = "Capital/Capital paid in";
+        assert_eq!(bs.get(path).unwrap().series_name, "RESPPLCP_N.WW");
+        assert_eq!(bs.get_by_series("RESPPLCP_N.WW").unwrap().path, path);
+        assert_eq!(bs.get("Capital").unwrap().path, "Capital");
+        assert!(bs.get("Capital/Unknown").is_none());
+        assert!(bs.get("Unknown/Capital paid in").is_none());
+        assert!(bs.get_by_series("UNKNOWN").is_none());
+
+        bs.get_mut(path).unwrap().value = 7;
+        assert_eq!(bs.get(path).unwrap().value, 7);
+
+        let capital = bs.get_concept_mut(&ConceptType::Capital);
+        capital.insert_concept("Capital/New/Concept", "NEW");
+        assert_eq!(
+            capital.get("Capital/New/Concept").unwrap().series_name,
+            "NEW"
+        );
+        capital.prune(Some(path), None);
+        assert!(capital.get("Capital/New").is_none());
+        assert_eq!(capital.get(path).unwrap().value, 7);
+
+        capital.get("Capital");
+        capital.for_each_mut(|c| {
+            if c.path == path {
+                c.series_name = "RENAMED".to_string();
+            }
+        });
+        assert!(capital.index.get().is_none());
+        assert_eq!(capital.get_by_series("RENAMED").unwrap().path, path);
+
+        // An earlier concept renamed to the series of a later one.
+        let mut bs = obs.values().nth(1).unwrap().clone();
+        let first = "Capital/Capital paid in";
+        let later = bs.get_concept(&ConceptType::Capital).children()[1].clone();
+        assert_eq!(
+            bs.get_by_series(&later.series_name).unwrap().path,
+            later.path
+        );
+        bs.get_mut(first).unwrap().series_name = later.series_name.clone();
+        assert_eq!(bs.get_by_series(&later.series_name).unwrap().path, first);
+        bs.get_mut(first).unwrap().series_name = "RESPPLCP_N.WX".to_string();
+        assert_eq!(bs.get_by_series("RESPPLCP_N.WX").unwrap().path, first);
+        assert!(bs.get_by_series("RESPPLCP_N.WW").is_none());
+    }
+
+    #[test]
+    fn rollup_test() {
+        let text = std::fs::read_to_string("tests/data/FRB_H41_2010.xml").unwrap();
+        let obs = fed::parse_h41_data(&text).unwrap();
+        let mut bs = obs.values().next().unwrap().clone();
+        bs.rollup();
+        assert!(bs.get_concept(&ConceptType::Assets).index.get().is_some());
+        assert!(!format!("{:?}", bs.get_concept(&ConceptType::Capital)).contains("index"));
+        let rolled_up: Vec<&Concept> = bs
+            .get_concept(&ConceptType::Assets)
+            .iter()
+            .filter(|c| c.status == ObservationStatus::Derived)
+            .collect();
+        assert!(!rolled_up.is_empty());
+        for c in rolled_up {
+            assert_eq!(c.series_name, UNDEFINED_SERIES_NAME);
+            assert_eq!(c.value, c.children.iter().map(|c| c.value).sum::<i64>());
+            assert!(c.leaves().all(|l| l.status != ObservationStatus::Derived));
+        }
+
+        let options = fed::ParseOptions {
+            rollup: true,
+            ..fed::ParseOptions::default()
+        };
+        let parsed = fed::parse_h41_data_with_options(&text, &options).unwrap();
+        assert_eq!(
+            serde_json::to_value(parsed.values().next().unwrap()).unwrap(),
+            serde_json::to_value(&bs).unwrap()
+        );
+    }
 }
@@ -0,0 +1,204 @@
+use chrono::NaiveDate;
+use std::fmt;
+
+use crate::types::{BalanceSheet, ConceptType, ObservationMap, ObservationStatus};
+
+/// Differences allowed between a published value and the value expected
+/// from other concepts, the largest of both applies.
+#[derive(Clone, Copy, Debug)]
+pub struct Tolerance {
+    /// absolute difference allowed, in the units of the values (millions).
+    pub absolute: i64,
+    /// difference allowed relative to the published value, e.g. 0.001 for 0.1%.
+    pub relative: f64,
+}
+
+impl Tolerance {
+    pub(crate) fn allows(&self, published: i64, expected: i64) -> bool {
+        let difference = (published - expected).abs();
+        difference <= self.absolute || difference as f64 <= self.relative * published.abs() as f64
+    }
+}
+
+impl Default for Tolerance {
+    /// Published totals are rounded on their own, so allow a few millions.
+    fn default() -> Tolerance {
+        Tolerance {
+            absolute: 5,
+            relative: 0.0,
+        }
+    }
+}
+
+/// Accounting inconsistency found in a balance sheet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ValidationIssue {
+    /// published value of an intermediate concept differs from the sum of its children.
+    SumMismatch {
+        /// path of the concept.
+        path: String,
+        /// published value.
+        value: i64,
+        /// sum of the values of its children.
+        children_sum: i64,
+    },
+    /// total assets differ from total liabilities plus capital.
+    Unbalanced {
+        /// total assets.
+        assets: i64,
+        /// total liabilities plus capital.
+        liabilities_and_capital: i64,
+    },
+    /// leaf concept without a value.
+    MissingLeaf {
+        /// path of the concept.
+        path: String,
+        /// status of the leaf, missing or not available.
+        status: ObservationStatus,
+    },
+}
+
+impl ValidationIssue {
+    /// Return true for issues breaking the accounting identities, i.e.
+    /// any issue but leaves without data.
+    pub fn is_inconsistency(&self) -> bool {
+        !matches!(self, ValidationIssue::MissingLeaf { .. })
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationIssue::SumMismatch {
+                path,
+                value,
+                children_sum,
+            } => write!(
+                f,
+                "{path}: value {value} differs from the sum of its children {children_sum}"
+            ),
+            ValidationIssue::Unbalanced {
+                assets,
+                liabilities_and_capital,
+            } => write!(
+                f,
+                "assets {assets} differ from liabilities and capital {liabilities_and_capital}"
+            ),
+            ValidationIssue::MissingLeaf { path, status } => write!(f, "{path}: {status}"),
+        }
+    }
+}
+
+impl BalanceSheet {
+    /// Check the accounting identities of the balance sheet: every
+    /// published intermediate value is the sum of its children and total
+    /// assets equal liabilities plus capital, within `tolerance`. Leaves
+    /// without data are reported too.
+    ///
+    /// Intermediate concepts without series are rolled up before checking,
+    /// see [`BalanceSheet::rollup`].
+    pub fn validate(&self, tolerance: &Tolerance) -> Vec<ValidationIssue> {
+        let mut bs = self.clone();
+        bs.rollup();
+
+        let mut issues = Vec::new();
+        for ctype in ConceptType::ALL.iter() {
+            for c in bs.get_concept(ctype).iter() {
+                if c.is_leaf() {
+                    if !c.status.has_value() {
+                        issues.push(ValidationIssue::MissingLeaf {
+                            path: c.path.clone(),
+                            status: c.status,
+                        });
+                    }
+                    continue;
+                }
+                if c.status != ObservationStatus::Available {
+                    continue;
+                }
+                let children_sum = c.children().iter().map(|c| c.value).sum();
+                if !tolerance.allows(c.value, children_sum) {
+                    issues.push(ValidationIssue::SumMismatch {
+                        path: c.path.clone(),
+                        value: c.value,
+                        children_sum,
+                    });
+                }
+            }
+        }
+
+        let assets = bs.get_concept(&ConceptType::Assets);
+        let liabilities = bs.get_concept(&ConceptType::Liabilities);
+        let capital = bs.get_concept(&ConceptType::Capital);
+        if [assets, liabilities, capital]
+            .iter()
+            .all(|c| c.status.has_value())
+        {
+            let liabilities_and_capital = liabilities.value + capital.value;
+            if !tolerance.allows(assets.value, liabilities_and_capital) {
+                issues.push(ValidationIssue::Unbalanced {
+                    assets: assets.value,
+                    liabilities_and_capital,
+                });
+            }
+        }
+        issues
+    }
+}
+
+/// Validate the balance sheet of every date, see [`BalanceSheet::validate`],
+/// returning the dates with issues.
+pub fn validate(
+    obs: &ObservationMap,
+    tolerance: &Tolerance,
+) -> Vec<(NaiveDate, Vec<ValidationIssue>)> {
+    obs.iter()
+        .map(|(date, bs)| (*date, bs.validate(tolerance)))
+        .filter(|(_, issues)| !issues.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fed::parse_h41_data;
+    use std::fs;
+
+    #[test]
+    fn validate_test() {
+        let text = fs::read_to_string("tests/data/FRB_H41_2006.xml").unwrap();
+        let obs = parse_h41_data(&text).unwrap();
+        let mut bs = obs.values().next().unwrap().clone();
+        assert!(bs.validate(&Tolerance::default()).is_empty());
+
+        let path = "Capital/Capital paid in";
+        bs.get_mut(path).unwrap().value += 100;
+        let issues = bs.validate(&Tolerance::default());
+        assert!(issues.contains(&ValidationIssue::SumMismatch {
+            path: "Capital".to_string(),
+            value: bs.get("Capital").unwrap().value,
+            children_sum: bs.get("Capital").unwrap().value + 100,
+        }));
+        assert!(!issues
+            .iter()
+            .any(|i| matches!(i, ValidationIssue::Unbalanced { .. })));
+
+        bs.get_mut("Capital").unwrap().value += 100;
+        let issues = bs.validate(&Tolerance::default());
+        assert_eq!(issues.len(), 1);
+        assert!(matches!(issues[0], ValidationIssue::Unbalanced { .. }));
+        let tolerance = Tolerance {
+            absolute: 0,
+            relative: 0.001,
+        };
+        assert!(bs.validate(&tolerance).is_empty());
+
+        bs.get_mut(path).unwrap().status = ObservationStatus::NotAvailable;
+        assert!(bs
+            .validate(&tolerance)
+            .contains(&ValidationIssue::MissingLeaf {
+                path: path.to_string(),
+                status: ObservationStatus::NotAvailable,
+            }));
+    }
+}
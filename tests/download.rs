@@ -0,0 +1,65 @@
+use mulligan::download::{FetchOptions, ReqwestClient};
+use mulligan::fed;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::thread;
+use std::time::Duration;
+
+const ZIP_BODY: &[u8] =
+    b"PK\x05\x06\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
+
+/// Stand-in for federalreserve.gov answering each connection with the next status.
+fn serve(statuses: Vec<u16>) -> (String, thread::JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let handle = thread::spawn(move || {
+        let mut requests = Vec::new();
+        for status in statuses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buffer = [0; 4096];
+            let n = stream.read(&mut buffer).unwrap();
+            requests.push(String::from_utf8_lossy(&buffer[..n]).to_string());
+            let body: &[u8] = if status == 200 { ZIP_BODY } else { b"" };
+            write!(
+                stream,
+                "HTTP/1.1 {status} X\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            )
+            .unwrap();
+            stream.write_all(body).unwrap();
+        }
+        requests
+    });
+    (base_url, handle)
+}
+
+fn options(base_url: String) -> FetchOptions {
+    FetchOptions {
+        base_url: Some(base_url),
+        timeout: Duration::from_secs(5),
+        retries: 2,
+        backoff: Duration::from_millis(10),
+    }
+}
+
+#[test]
+fn fetch_h41_to_memory() {
+    let (base_url, server) = serve(vec![200]);
+    let bytes = fed::fetch_h41(&ReqwestClient::default(), &options(base_url)).unwrap();
+    assert_eq!(bytes, ZIP_BODY);
+    let requests = server.join().unwrap();
+    assert!(requests[0].starts_with(&format!("GET {} ", fed::H41_FED_DOWNLOAD_PATH)));
+}
+
+#[test]
+fn fetch_h41_to_path_with_retries() {
+    let (base_url, server) = serve(vec![503, 500, 200]);
+    let path = std::env::temp_dir().join(format!(
+        "mulligan_fetch_h41_test_{}.zip",
+        std::process::id()
+    ));
+    fed::fetch_h41_to_path(&ReqwestClient::default(), &options(base_url), &path).unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), ZIP_BODY);
+    assert_eq!(server.join().unwrap().len(), 3);
+    std::fs::remove_file(path).unwrap();
+}
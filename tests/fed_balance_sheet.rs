@@ -1,4 +1,5 @@
 use mulligan::fed;
+use mulligan::validate::{self, Tolerance};
 use mulligan::{ConceptType, NaiveDate};
 
 const FED_XML_2020_DATA_PATH: &str = "tests/data/FRB_H41_2020.xml";
@@ -19,6 +20,7 @@ const ASSETS_20200527: &str = r#"
 Assets                                                                   7097316
   Central Bank Liquidity Swaps                                      
     Central bank liquidity swaps                                          448946
+  Foreign currency denominated assets                                      20564
   Liquidity and Credit Facilities                                   
     Corporate Credit Facilities LLC                                        34853
     Loans                                                                 106896
@@ -44,6 +46,8 @@ Assets                                                                   7097316
       Inflation compensation                                               36843
       Notes and bonds, inflation-indexed                                  257451
       Notes and bonds, nominal                                           3489174
+  Unamortized discounts on securities held outright                        -5500
+  Unamortized premiums on securities held outright                        304256
 "#;
 
 const LIABILITIES_20200527: &str = r#"
@@ -54,12 +58,12 @@ Liabilities                                                              7058402
     Other                                                                 179062
     Other deposits held by depository institutions                       3317688
     U.S. Treasury, General Account                                       1326897
-  Earnings remittances due to the U.S. Treasury                             1160
   Federal Reserve notes, net of F.R. Bank holdings                       1899514
   Other liabilities and accrued dividends (Includes the liability fo        8160
   Reverse repurchase agreements                                           243976
     Foreign official and international accounts                           239650
     Others                                                                  4326
+  Treasury Contribution to Credit Facilities (Effective 2020-05-07)        66500
 "#;
 
 const CAPITAL_20200527: &str = r#"
@@ -72,6 +76,7 @@ const ASSETS_20200520: &str = r#"
 Assets                                                                   7037258
   Central Bank Liquidity Swaps                                      
     Central bank liquidity swaps                                          446103
+  Foreign currency denominated assets                                      20595
   Liquidity and Credit Facilities                                   
     Corporate Credit Facilities LLC                                         1801
     Loans                                                                 108577
@@ -97,6 +102,8 @@ Assets                                                                   7037258
       Inflation compensation                                               36797
       Notes and bonds, inflation-indexed                                  255266
       Notes and bonds, nominal                                           3471224
+  Unamortized discounts on securities held outright                        -5691
+  Unamortized premiums on securities held outright                        304104
 "#;
 
 const LIABILITIES_20200520: &str = r#"
@@ -107,12 +114,12 @@ Liabilities                                                              6998365
     Other                                                                 267066
     Other deposits held by depository institutions                       3304221
     U.S. Treasury, General Account                                       1193297
-  Earnings remittances due to the U.S. Treasury                             1530
   Federal Reserve notes, net of F.R. Bank holdings                       1890000
   Other liabilities and accrued dividends (Includes the liability fo       11644
   Reverse repurchase agreements                                           266649
     Foreign official and international accounts                           256923
     Others                                                                  9726
+  Treasury Contribution to Credit Facilities (Effective 2020-05-07)        49000
 "#;
 
 const CAPITAL_20200520: &str = r#"
@@ -125,6 +132,7 @@ const ASSETS_20200513: &str = r#"
 Assets                                                                   6934227
   Central Bank Liquidity Swaps                                      
     Central bank liquidity swaps                                          440934
+  Foreign currency denominated assets                                      20484
   Liquidity and Credit Facilities                                   
     Corporate Credit Facilities LLC                                          305
     Loans                                                                 114927
@@ -149,6 +157,8 @@ Assets                                                                   6934227
       Inflation compensation                                               36592
       Notes and bonds, inflation-indexed                                  252016
       Notes and bonds, nominal                                           3442616
+  Unamortized discounts on securities held outright                        -5354
+  Unamortized premiums on securities held outright                        297516
 "#;
 
 const LIABILITIES_20200513: &str = r#"
@@ -159,12 +169,12 @@ Liabilities                                                              6895336
     Other                                                                 246034
     Other deposits held by depository institutions                       3263431
     U.S. Treasury, General Account                                       1138172
-  Earnings remittances due to the U.S. Treasury                             2095
   Federal Reserve notes, net of F.R. Bank holdings                       1881319
   Other liabilities and accrued dividends (Includes the liability fo       21114
   Reverse repurchase agreements                                           281150
     Foreign official and international accounts                           267325
     Others                                                                 13825
+  Treasury Contribution to Credit Facilities (Effective 2020-05-07)        47500
 "#;
 
 const CAPITAL_20200513: &str = r#"
@@ -177,6 +187,7 @@ const ASSETS_20200506: &str = r#"
 Assets                                                                   6721420
   Central Bank Liquidity Swaps                                      
     Central bank liquidity swaps                                          444885
+  Foreign currency denominated assets                                      20521
   Liquidity and Credit Facilities                                   
     Loans                                                                 113342
       Money Market Mutual Fund Liquidity Facility (Post 2020-03-18)        42763
@@ -200,6 +211,8 @@ Assets                                                                   6721420
       Inflation compensation                                               36365
       Notes and bonds, inflation-indexed                                  248266
       Notes and bonds, nominal                                           3409516
+  Unamortized discounts on securities held outright                        -5534
+  Unamortized premiums on securities held outright                        286502
 "#;
 
 const LIABILITIES_20200506: &str = r#"
@@ -210,7 +223,6 @@ Liabilities                                                              6682549
     Other                                                                 193329
     Other deposits held by depository institutions                       3165606
     U.S. Treasury, General Account                                       1143268
-  Earnings remittances due to the U.S. Treasury                             2029
   Federal Reserve notes, net of F.R. Bank holdings                       1873325
   Other liabilities and accrued dividends (Includes the liability fo       25208
   Reverse repurchase agreements                                           265206
@@ -228,6 +240,7 @@ const ASSETS_20200429: &str = r#"
 Assets                                                                   6655929
   Central Bank Liquidity Swaps                                      
     Central bank liquidity swaps                                          438953
+  Foreign currency denominated assets                                      20525
   Liquidity and Credit Facilities                                   
     Loans                                                                 123028
       Money Market Mutual Fund Liquidity Facility (Post 2020-03-18)        46277
@@ -251,6 +264,8 @@ Assets                                                                   6655929
       Inflation compensation                                               36038
       Notes and bonds, inflation-indexed                                  242290
       Notes and bonds, nominal                                           3367047
+  Unamortized discounts on securities held outright                        -5711
+  Unamortized premiums on securities held outright                        283240
 "#;
 
 const LIABILITIES_20200429: &str = r#"
@@ -261,7 +276,6 @@ Liabilities                                                              6617091
     Other                                                                 204070
     Other deposits held by depository institutions                       3163513
     U.S. Treasury, General Account                                       1076232
-  Earnings remittances due to the U.S. Treasury                             1933
   Federal Reserve notes, net of F.R. Bank holdings                       1862131
   Other liabilities and accrued dividends (Includes the liability fo       24279
   Reverse repurchase agreements                                           269106
@@ -413,6 +427,7 @@ fn balance_sheets_2020() {
 
 const ASSETS_20100310: &str = r#"
 Assets                                                                   2282548
+  Foreign currency denominated assets                                      24896
   Liquidity and Credit Facilities                                   
     Loans                                                                  83791
       Credit extended to American International Group, Inc., Net           24845
@@ -445,6 +460,8 @@ Assets                                                                   2282548
       Inflation compensation                                                5519
       Notes and bonds, inflation-indexed                                   43777
       Notes and bonds, nominal                                            708872
+  Unamortized discounts on securities held outright                        -3368
+  Unamortized premiums on securities held outright                         53212
 "#;
 
 const LIABILITIES_20100310: &str = r#"
@@ -503,6 +520,7 @@ fn balance_sheets_2010() {
 
 const ASSETS_20060308: &str = r#"
 Assets                                                                    840528
+  Foreign currency denominated assets                                      19048
   Liquidity and Credit Facilities                                   
     Loans                                                                     43
       Seasonal credit                                                         43
@@ -520,6 +538,8 @@ Assets                                                                    840528
       Inflation compensation                                                3160
       Notes and bonds, inflation-indexed                                   22196
       Notes and bonds, nominal                                            456077
+  Unamortized discounts on securities held outright                        -3084
+  Unamortized premiums on securities held outright                          8861
 "#;
 
 const LIABILITIES_20060308: &str = r#"
@@ -574,3 +594,26 @@ fn balance_sheets_2006() {
     );
     assert_by_lines(CAPITAL_20060308, &displayed_capital);
 }
+
+#[test]
+fn accounting_consistency() {
+    for path in [
+        FED_XML_2006_DATA_PATH,
+        FED_XML_2010_DATA_PATH,
+        FED_XML_2020_DATA_PATH,
+    ] {
+        let h41_data_text = std::fs::read_to_string(path).unwrap();
+        let observations = fed::parse_h41_data(&h41_data_text).unwrap();
+        for (date, issues) in validate::validate(&observations, &Tolerance::default()) {
+            let inconsistencies: Vec<String> = issues
+                .iter()
+                .filter(|i| i.is_inconsistency())
+                .map(|i| i.to_string())
+                .collect();
+            assert!(
+                inconsistencies.is_empty(),
+                "{path} {date}: {inconsistencies:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const FEDPARSER: &str = env!("CARGO_BIN_EXE_fedparser");
+const FED_XML_2010_DATA_PATH: &str = "tests/data/FRB_H41_2010.xml";
+
+fn fedparser(args: &[&str]) -> Output {
+    Command::new(FEDPARSER).args(args).output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> TempDir {
+        let dir = std::env::temp_dir().join(format!("mulligan_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        TempDir(dir)
+    }
+
+    fn path(&self, file: &str) -> String {
+        self.0.join(file).to_str().unwrap().to_string()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn parse_and_export_test() {
+    let dir = TempDir::new("fedparser_export");
+    let parsed = dir.path("parsed");
+    let output = fedparser(&[
+        "parse",
+        "--input",
+        FED_XML_2010_DATA_PATH,
+        "--output",
+        &parsed,
+    ]);
+    assert!(output.status.success());
+    let observations = dir.path("parsed/observations.json");
+    assert!(Path::new(&observations).is_file());
+
+    let exported = dir.path("exported");
+    let output = fedparser(&[
+        "export",
+        "--input",
+        &observations,
+        "--output",
+        &exported,
+        "--formats",
+        "csv,long-csv,snapshot",
+    ]);
+    assert!(output.status.success());
+    for file in [
+        "assets.csv",
+        "liabilities.csv",
+        "capital.csv",
+        "observations_long.csv",
+        "observations.snapshot",
+    ] {
+        assert!(Path::new(&dir.path(&format!("exported/{file}"))).is_file());
+    }
+
+    let capital = fs::read_to_string(dir.path("exported/capital.csv")).unwrap();
+    assert!(capital.starts_with(
+        "date,Capital paid in,Other capital accounts,Surplus\n2010-01-06,25651,1330,25166\n"
+    ));
+    let long = fs::read_to_string(dir.path("exported/observations_long.csv")).unwrap();
+    assert!(long.starts_with("date,statement,path,series_name,depth,is_leaf,value,status\n"));
+}
+
+#[test]
+fn show_test() {
+    let dir = TempDir::new("fedparser_show");
+    let parsed = dir.path("parsed");
+    assert!(fedparser(&[
+        "parse",
+        "--input",
+        FED_XML_2010_DATA_PATH,
+        "--output",
+        &parsed
+    ])
+    .status
+    .success());
+    let observations = dir.path("parsed/observations.json");
+
+    let output = fedparser(&[
+        "show",
+        "--input",
+        &observations,
+        "--date",
+        "2010-03-03",
+        "--statements",
+        "capital",
+    ]);
+    assert!(output.status.success());
+    let text = stdout(&output);
+    assert!(text.contains("Balance sheet 2010-03-03"));
+    assert!(text.contains("53276"));
+    assert!(text.contains("Capital paid in"));
+
+    let output = fedparser(&["show", "--input", &observations, "--date", "1999-01-01"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn diff_and_explain_test() {
+    let output = fedparser(&[
+        "diff",
+        "--input",
+        FED_XML_2010_DATA_PATH,
+        "--period",
+        "1w",
+        "--statements",
+        "capital",
+    ]);
+    assert!(output.status.success());
+    let text = stdout(&output);
+    assert!(text.contains("2010-03-03"));
+    assert!(text.contains("2010-03-10"));
+    assert!(text.contains("-458"));
+
+    let output = fedparser(&[
+        "explain",
+        "--input",
+        FED_XML_2010_DATA_PATH,
+        "--concept",
+        "Capital",
+        "--period",
+        "1w",
+    ]);
+    assert!(output.status.success());
+    let text = stdout(&output);
+    assert!(text.starts_with("Capital changed -458 from 2010-03-03 to 2010-03-10"));
+    assert!(text.contains("Capital/Other capital accounts"));
+}